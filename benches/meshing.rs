@@ -0,0 +1,54 @@
+//! Compares the culled and greedy meshers on generated terrain
+//!
+//! Run with: `cargo bench --bench meshing`
+
+use std::time::Instant;
+
+use noise::Perlin;
+use voxel_game::{mesh_chunk, Chunk, MeshingMode, World};
+
+const RADIUS: i32 = 4;
+const ITERATIONS: u32 = 5;
+
+fn main() {
+    let perlin = Perlin::new(42);
+    let mut world = World::new();
+    for x in -RADIUS..RADIUS {
+        for z in -RADIUS..RADIUS {
+            let mut chunk = Chunk::new((x, z));
+            chunk.generate_terrain(&perlin);
+            world.insert_chunk(chunk);
+        }
+    }
+
+    println!("Meshing {} chunks, {} iterations each", world.chunk_count(), ITERATIONS);
+    println!("----------------------------------------");
+    let culled = run("culled", &world, MeshingMode::Culled);
+    let greedy = run("greedy", &world, MeshingMode::Greedy);
+    println!(
+        "greedy/culled vertex ratio: {:.3}",
+        greedy as f64 / culled as f64
+    );
+}
+
+/// Meshes every chunk, prints timing and returns the total vertex count
+fn run(name: &str, world: &World, mode: MeshingMode) -> usize {
+    let start = Instant::now();
+    let mut vertices = 0;
+    let mut indices = 0;
+    for _ in 0..ITERATIONS {
+        vertices = 0;
+        indices = 0;
+        for chunk in world.chunks() {
+            let mesh = mesh_chunk(chunk, &world.neighbors(chunk.position), mode);
+            vertices += mesh.vertices.len();
+            indices += mesh.indices.len();
+        }
+    }
+    let per_chunk = start.elapsed() / (ITERATIONS * world.chunk_count() as u32);
+    println!(
+        "{:>6}: {:>8} vertices, {:>8} indices, {:?} per chunk",
+        name, vertices, indices, per_chunk
+    );
+    vertices
+}
@@ -0,0 +1,61 @@
+//! Renders a top-down biome map for a seed
+//!
+//! Usage: cargo run --example biome_map -- [seed] [blocks per pixel] [output.png]
+//!
+//! Each pixel is coloured by biome and shaded by surface height, centred on
+//! the world origin. A count of each biome is printed so distributions can
+//! be compared between seeds and settings.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufWriter;
+
+use voxel_game::worldgen::Biome;
+use voxel_game::{GeneratorSettings, TerrainGenerator};
+
+const IMAGE_SIZE: u32 = 512;
+/// Surface height shaded brightest; generated terrain stays well below it
+const SHADE_HEIGHT: f32 = 64.0;
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let seed = args.next().map(|s| s.parse().expect("seed must be a number")).unwrap_or(42);
+    let scale: i32 = args
+        .next()
+        .map(|s| s.parse().expect("blocks per pixel must be a number"))
+        .unwrap_or(8);
+    let output = args.next().unwrap_or_else(|| format!("biome_map_{}.png", seed));
+
+    let generator = TerrainGenerator::new(GeneratorSettings { seed, ..Default::default() });
+    let half = IMAGE_SIZE as i32 / 2;
+
+    let mut pixels = Vec::with_capacity((IMAGE_SIZE * IMAGE_SIZE * 3) as usize);
+    let mut counts: HashMap<Biome, usize> = HashMap::new();
+    for row in 0..IMAGE_SIZE as i32 {
+        for column in 0..IMAGE_SIZE as i32 {
+            let x = (column - half) * scale;
+            let z = (row - half) * scale;
+            let biome = generator.biomes().biome_at(x, z);
+            *counts.entry(biome).or_insert(0) += 1;
+
+            let height = generator.surface_height(x, z) as f32 / SHADE_HEIGHT;
+            let shade = 0.6 + 0.8 * height;
+            pixels.extend(biome.map_color().iter().map(|&c| (c as f32 * shade).min(255.0) as u8));
+        }
+    }
+
+    let file = File::create(&output).expect("failed to create output file");
+    let mut encoder = png::Encoder::new(BufWriter::new(file), IMAGE_SIZE, IMAGE_SIZE);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().expect("failed to write PNG header");
+    writer.write_image_data(&pixels).expect("failed to write PNG data");
+
+    let extent = IMAGE_SIZE as i32 * scale;
+    println!("Seed {}: {}x{} blocks, written to {}", seed, extent, extent, output);
+    let total = (IMAGE_SIZE * IMAGE_SIZE) as f32;
+    for biome in Biome::ALL {
+        let count = counts.get(&biome).copied().unwrap_or(0);
+        println!("{:>10?}: {:5.1}%", biome, count as f32 / total * 100.0);
+    }
+}
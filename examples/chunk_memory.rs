@@ -0,0 +1,68 @@
+//! Measures how much memory generated chunks take
+//!
+//! Usage: cargo run --release --example chunk_memory -- [settings.toml] [radius in chunks]
+//!
+//! Generates a square of chunks around the origin and compares the paletted
+//! sections against a dense array of one byte per block, both for the whole
+//! chunk height and for only the sections that hold any blocks. The spread of
+//! palette index widths shows how varied the generated sections are.
+
+use std::collections::BTreeMap;
+
+use voxel_game::{
+    Chunk, GeneratorSettings, TerrainGenerator, WorldGenerator, CHUNK_SIZE, MAX_Y, MIN_Y,
+    SECTION_COUNT, SECTION_SIZE,
+};
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let settings = match args.next() {
+        Some(path) => GeneratorSettings::load(&path).expect("failed to load generator settings"),
+        None => GeneratorSettings::default(),
+    };
+    let radius: i32 = args
+        .next()
+        .map(|s| s.parse().expect("radius must be a number"))
+        .unwrap_or(4);
+
+    let generator = TerrainGenerator::new(settings);
+    let mut chunks = 0;
+    let mut paletted = 0;
+    let mut sections = 0;
+    let mut widths: BTreeMap<u32, usize> = BTreeMap::new();
+
+    for chunk_x in -radius..radius {
+        for chunk_z in -radius..radius {
+            let mut chunk = Chunk::new((chunk_x, chunk_z));
+            generator.generate_chunk(&mut chunk);
+            chunks += 1;
+            paletted += chunk.memory_usage();
+            for section in (0..SECTION_COUNT).filter_map(|index| chunk.section(index)) {
+                sections += 1;
+                *widths.entry(section.blocks().bits_per_block()).or_insert(0) += 1;
+            }
+        }
+    }
+
+    let section_bytes = SECTION_SIZE * SECTION_SIZE * SECTION_SIZE;
+    let full = CHUNK_SIZE * (MAX_Y - MIN_Y) as usize * CHUNK_SIZE;
+    let kib = |bytes: usize| bytes as f64 / chunks as f64 / 1024.0;
+    println!("Memory per chunk over {} chunks", chunks);
+    println!("----------------------------------------");
+    println!("{:<28}{:>10.1} KiB", "dense, full height", full as f64 / 1024.0);
+    println!("{:<28}{:>10.1} KiB", "dense, allocated sections", kib(sections * section_bytes));
+    println!("{:<28}{:>10.1} KiB", "paletted", kib(paletted));
+    println!();
+    println!(
+        "{:.1} sections allocated per chunk, by index width:",
+        sections as f64 / chunks as f64
+    );
+    for (bits, count) in &widths {
+        println!(
+            "  {:>2} bits: {:>6} ({:.1}%)",
+            bits,
+            count,
+            *count as f64 / sections as f64 * 100.0
+        );
+    }
+}
@@ -0,0 +1,65 @@
+//! Precomputes erosion for a square of regions around the origin
+//!
+//! Usage: cargo run --release --example erode_regions -- [settings.toml] [radius in regions] [output dir]
+//!
+//! Erosion is simulated per 128x128 block region, which takes a moment per
+//! region. Setting `erosion_cache` to the output directory in the settings
+//! file lets the game load these regions instead of simulating them while
+//! streaming chunks. Regions are only used with the settings they were
+//! computed for.
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use voxel_game::{GeneratorSettings, TerrainGenerator};
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let settings = match args.next() {
+        Some(path) => GeneratorSettings::load(&path).expect("failed to load generator settings"),
+        None => GeneratorSettings::default(),
+    };
+    let radius: i32 = args
+        .next()
+        .map(|s| s.parse().expect("radius must be a number"))
+        .unwrap_or(2);
+    let output = PathBuf::from(args.next().unwrap_or_else(|| "erosion".to_string()));
+    std::fs::create_dir_all(&output).expect("failed to create output directory");
+
+    // Always simulate, even over regions already in the settings' cache
+    let generator = TerrainGenerator::new(GeneratorSettings {
+        erosion: true,
+        erosion_cache: None,
+        ..settings.clone()
+    });
+    let regions: Vec<(i32, i32)> = (-radius..radius)
+        .flat_map(|x| (-radius..radius).map(move |z| (x, z)))
+        .collect();
+
+    println!(
+        "Eroding {} regions for seed {} into {}",
+        regions.len(),
+        settings.seed,
+        output.display()
+    );
+    let start = Instant::now();
+    let next = AtomicUsize::new(0);
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    std::thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| {
+                while let Some(&position) = regions.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    let region = generator.eroded_region(position).unwrap();
+                    region.save(&output).expect("failed to write region");
+                    let (carved, deposited) = region.extremes();
+                    println!(
+                        "  region {:?}: carved up to {:.1}, deposited up to {:.1} blocks",
+                        position, carved, deposited
+                    );
+                }
+            });
+        }
+    });
+    println!("Done in {:.1?}", start.elapsed());
+}
@@ -0,0 +1,80 @@
+//! Reports how ores are distributed across generated terrain
+//!
+//! Usage: cargo run --release --example ore_stats -- [settings.toml] [radius in chunks]
+//!
+//! Generates a square of chunks around the origin and prints, for each
+//! configured ore, the total block count, the average per chunk and the
+//! heights it was found at.
+
+use std::collections::HashMap;
+
+use voxel_game::{
+    BlockType, Chunk, GeneratorSettings, TerrainGenerator, WorldGenerator, CHUNK_SIZE, MAX_Y,
+    MIN_Y,
+};
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let settings = match args.next() {
+        Some(path) => GeneratorSettings::load(&path).expect("failed to load generator settings"),
+        None => GeneratorSettings::default(),
+    };
+    let radius: i32 = args
+        .next()
+        .map(|s| s.parse().expect("radius must be a number"))
+        .unwrap_or(8);
+
+    let generator = TerrainGenerator::new(settings.clone());
+    let mut counts: HashMap<BlockType, usize> = HashMap::new();
+    let mut heights: HashMap<BlockType, (i32, i32)> = HashMap::new();
+    let mut chunks = 0;
+
+    for chunk_x in -radius..radius {
+        for chunk_z in -radius..radius {
+            let mut chunk = Chunk::new((chunk_x, chunk_z));
+            generator.generate_chunk(&mut chunk);
+            chunks += 1;
+            for x in 0..CHUNK_SIZE {
+                for y in MIN_Y..MAX_Y {
+                    for z in 0..CHUNK_SIZE {
+                        let block = chunk.get_block_at(x, y, z).unwrap();
+                        *counts.entry(block).or_insert(0) += 1;
+                        let range = heights.entry(block).or_insert((y, y));
+                        range.0 = range.0.min(y);
+                        range.1 = range.1.max(y);
+                    }
+                }
+            }
+        }
+    }
+    for (position, e) in generator.take_erosion_errors() {
+        eprintln!("Ignoring erosion region {:?}: {}", position, e);
+    }
+
+    println!("Ore statistics for seed {} over {} chunks", settings.seed, chunks);
+    println!("==========================================\n");
+    let stone = counts.get(&BlockType::Stone).copied().unwrap_or(0);
+    println!("Stone: {} blocks\n", stone);
+
+    println!(
+        "{:<12} {:>9} {:>10} {:>9} {:>12} {:>10}",
+        "Ore", "Blocks", "Per chunk", "% rock", "Found at", "Config"
+    );
+    println!("{}", "-".repeat(67));
+    for ore in &settings.ores {
+        let count = counts.get(&ore.block).copied().unwrap_or(0);
+        let found = match heights.get(&ore.block) {
+            Some((low, high)) if count > 0 => format!("{}..={}", low, high),
+            _ => "-".to_string(),
+        };
+        println!(
+            "{:<12} {:>9} {:>10.1} {:>8.3}% {:>12} {:>10}",
+            format!("{:?}", ore.block),
+            count,
+            count as f64 / chunks as f64,
+            count as f64 / (stone + count).max(1) as f64 * 100.0,
+            found,
+            format!("{}..={}", ore.min_height, ore.max_height),
+        );
+    }
+}
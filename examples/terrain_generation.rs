@@ -1,11 +1,11 @@
-/// Example demonstrating the voxel game engine's core features
-/// 
-/// This example shows:
-/// - Creating a chunk with procedural terrain
-/// - Block type management
-/// - Basic terrain generation
+//! Example demonstrating the voxel game engine's core features
+//! 
+//! This example shows:
+//! - Creating a chunk with procedural terrain
+//! - Block type management
+//! - Basic terrain generation
 
-use voxel_game::{Chunk, BlockType, CHUNK_SIZE, CHUNK_HEIGHT};
+use voxel_game::{Chunk, BlockType, CHUNK_SIZE, MAX_Y, MIN_Y};
 use noise::Perlin;
 
 fn main() {
@@ -15,7 +15,8 @@ fn main() {
     // Create a new chunk at world position (0, 0)
     let mut chunk = Chunk::new((0, 0));
     println!("Created chunk at position (0, 0)");
-    println!("Chunk dimensions: {}x{}x{} blocks\n", CHUNK_SIZE, CHUNK_HEIGHT, CHUNK_SIZE);
+    let height = (MAX_Y - MIN_Y) as usize;
+    println!("Chunk dimensions: {}x{}x{} blocks\n", CHUNK_SIZE, height, CHUNK_SIZE);
 
     // Generate terrain using Perlin noise
     let perlin = Perlin::new(42);
@@ -25,9 +26,9 @@ fn main() {
     // Display some statistics
     let mut block_counts = std::collections::HashMap::new();
     for x in 0..CHUNK_SIZE {
-        for y in 0..CHUNK_HEIGHT {
+        for y in MIN_Y..MAX_Y {
             for z in 0..CHUNK_SIZE {
-                if let Some(block) = chunk.get_block(x, y, z) {
+                if let Some(block) = chunk.get_block_at(x, y, z) {
                     *block_counts.entry(block).or_insert(0) += 1;
                 }
             }
@@ -37,7 +38,7 @@ fn main() {
     println!("\nBlock statistics:");
     println!("-----------------");
     for (block_type, count) in block_counts.iter() {
-        let percentage = (*count as f32 / (CHUNK_SIZE * CHUNK_HEIGHT * CHUNK_SIZE) as f32) * 100.0;
+        let percentage = (*count as f32 / (CHUNK_SIZE * height * CHUNK_SIZE) as f32) * 100.0;
         println!("{:?}: {} blocks ({:.1}%)", block_type, count, percentage);
     }
 
@@ -50,6 +51,19 @@ fn main() {
         BlockType::Dirt,
         BlockType::Stone,
         BlockType::Water,
+        BlockType::Sand,
+        BlockType::Snow,
+        BlockType::Bedrock,
+        BlockType::CoalOre,
+        BlockType::IronOre,
+        BlockType::GoldOre,
+        BlockType::DiamondOre,
+        BlockType::Log,
+        BlockType::Leaves,
+        BlockType::TallGrass,
+        BlockType::Flower,
+        BlockType::Planks,
+        BlockType::Cobblestone,
     ] {
         println!(
             "{:?}: Solid={}, Transparent={}, Color={:?}",
@@ -63,8 +77,8 @@ fn main() {
     // Sample some blocks from the chunk
     println!("\nSample blocks from the chunk:");
     println!("------------------------------");
-    for y in (0..CHUNK_HEIGHT).step_by(10) {
-        if let Some(block) = chunk.get_block(8, y, 8) {
+    for y in (MIN_Y..MAX_Y).step_by(10) {
+        if let Some(block) = chunk.get_block_at(8, y, 8) {
             println!("Position (8, {}, 8): {:?}", y, block);
         }
     }
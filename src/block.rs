@@ -1,22 +1,111 @@
+use serde::{Deserialize, Serialize};
+
 /// Represents different types of blocks in the voxel world
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum BlockType {
     Air,
     Grass,
     Dirt,
     Stone,
     Water,
+    Sand,
+    Snow,
+    Bedrock,
+    CoalOre,
+    IronOre,
+    GoldOre,
+    DiamondOre,
+    Log,
+    Leaves,
+    TallGrass,
+    Flower,
+    Planks,
+    Cobblestone,
 }
 
 impl BlockType {
-    /// Returns true if the block is solid (not air or water)
+    /// Every block type, in declaration order, which is also default ID order
+    pub const ALL: &'static [BlockType] = &[
+        BlockType::Air,
+        BlockType::Grass,
+        BlockType::Dirt,
+        BlockType::Stone,
+        BlockType::Water,
+        BlockType::Sand,
+        BlockType::Snow,
+        BlockType::Bedrock,
+        BlockType::CoalOre,
+        BlockType::IronOre,
+        BlockType::GoldOre,
+        BlockType::DiamondOre,
+        BlockType::Log,
+        BlockType::Leaves,
+        BlockType::TallGrass,
+        BlockType::Flower,
+        BlockType::Planks,
+        BlockType::Cobblestone,
+    ];
+
+    /// Numeric ID of the block in newly created worlds
+    ///
+    /// Saves keep their own ID table (see [`crate::save::BlockIds`]), so
+    /// this numbering may change between builds.
+    pub fn id(self) -> u16 {
+        self as u16
+    }
+
+    /// The block with the given default ID
+    pub fn from_id(id: u16) -> Option<Self> {
+        Self::ALL.get(id as usize).copied()
+    }
+
+    /// Stable name of the block, used to map IDs in saves
+    pub fn name(self) -> &'static str {
+        match self {
+            BlockType::Air => "air",
+            BlockType::Grass => "grass",
+            BlockType::Dirt => "dirt",
+            BlockType::Stone => "stone",
+            BlockType::Water => "water",
+            BlockType::Sand => "sand",
+            BlockType::Snow => "snow",
+            BlockType::Bedrock => "bedrock",
+            BlockType::CoalOre => "coal_ore",
+            BlockType::IronOre => "iron_ore",
+            BlockType::GoldOre => "gold_ore",
+            BlockType::DiamondOre => "diamond_ore",
+            BlockType::Log => "log",
+            BlockType::Leaves => "leaves",
+            BlockType::TallGrass => "tall_grass",
+            BlockType::Flower => "flower",
+            BlockType::Planks => "planks",
+            BlockType::Cobblestone => "cobblestone",
+        }
+    }
+
+    /// The block with the given name
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|block| block.name() == name)
+    }
+
+    /// Returns true if the block is solid (not air, water or plants)
     pub fn is_solid(&self) -> bool {
-        !matches!(self, BlockType::Air | BlockType::Water)
+        !matches!(
+            self,
+            BlockType::Air | BlockType::Water | BlockType::TallGrass | BlockType::Flower
+        )
     }
 
     /// Returns true if the block is transparent
     pub fn is_transparent(&self) -> bool {
-        matches!(self, BlockType::Air | BlockType::Water)
+        matches!(
+            self,
+            BlockType::Air
+                | BlockType::Water
+                | BlockType::Leaves
+                | BlockType::TallGrass
+                | BlockType::Flower
+        )
     }
 
     /// Returns the color of the block as RGB
@@ -27,6 +116,19 @@ impl BlockType {
             BlockType::Dirt => [0.6, 0.4, 0.2],
             BlockType::Stone => [0.5, 0.5, 0.5],
             BlockType::Water => [0.2, 0.4, 0.8],
+            BlockType::Sand => [0.86, 0.8, 0.55],
+            BlockType::Snow => [0.95, 0.96, 0.98],
+            BlockType::Bedrock => [0.2, 0.2, 0.22],
+            BlockType::CoalOre => [0.25, 0.25, 0.25],
+            BlockType::IronOre => [0.7, 0.55, 0.45],
+            BlockType::GoldOre => [0.9, 0.8, 0.3],
+            BlockType::DiamondOre => [0.45, 0.85, 0.9],
+            BlockType::Log => [0.4, 0.28, 0.15],
+            BlockType::Leaves => [0.15, 0.5, 0.15],
+            BlockType::TallGrass => [0.35, 0.75, 0.25],
+            BlockType::Flower => [0.9, 0.3, 0.4],
+            BlockType::Planks => [0.72, 0.56, 0.34],
+            BlockType::Cobblestone => [0.42, 0.42, 0.4],
         }
     }
 }
@@ -41,6 +143,8 @@ mod tests {
         assert!(BlockType::Grass.is_solid());
         assert!(BlockType::Stone.is_solid());
         assert!(!BlockType::Water.is_solid());
+        assert!(BlockType::Leaves.is_solid());
+        assert!(!BlockType::TallGrass.is_solid());
     }
 
     #[test]
@@ -48,5 +152,24 @@ mod tests {
         assert!(BlockType::Air.is_transparent());
         assert!(!BlockType::Grass.is_transparent());
         assert!(BlockType::Water.is_transparent());
+        assert!(BlockType::Leaves.is_transparent());
+        assert!(!BlockType::Log.is_transparent());
+    }
+
+    #[test]
+    fn test_block_ids_round_trip() {
+        for (id, &block) in BlockType::ALL.iter().enumerate() {
+            assert_eq!(block.id() as usize, id);
+            assert_eq!(BlockType::from_id(block.id()), Some(block));
+        }
+        assert_eq!(BlockType::from_id(BlockType::ALL.len() as u16), None);
+    }
+
+    #[test]
+    fn test_block_names_are_unique() {
+        for &block in BlockType::ALL {
+            assert_eq!(BlockType::from_name(block.name()), Some(block));
+        }
+        assert_eq!(BlockType::from_name("Stone"), None);
     }
 }
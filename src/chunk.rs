@@ -1,43 +1,228 @@
 use crate::block::BlockType;
+use crate::palette::PalettedBlocks;
 
-/// Size of a chunk in blocks (width, height, depth)
+/// Width and depth of a chunk in blocks
 pub const CHUNK_SIZE: usize = 16;
-pub const CHUNK_HEIGHT: usize = 64;
+/// Lowest block layer of a chunk; layers below 0 use the `*_at` accessors
+pub const MIN_Y: i32 = -64;
+/// One past the highest block layer of a chunk; a chunk is
+/// `MAX_Y - MIN_Y` blocks tall
+pub const MAX_Y: i32 = 320;
+/// Edge length of the cubic sections a chunk is split into
+pub const SECTION_SIZE: usize = 16;
+/// Number of sections stacked in a chunk, from `MIN_Y` to `MAX_Y`
+pub const SECTION_COUNT: usize = (MAX_Y - MIN_Y) as usize / SECTION_SIZE;
+
+const SECTION_VOLUME: usize = SECTION_SIZE * SECTION_SIZE * SECTION_SIZE;
+
+/// A 16x16x16 cube of blocks within a chunk
+///
+/// Blocks are kept as a palette with bit-packed indices, so a section of
+/// stone with a few ores in it takes a few hundred bytes rather than 4KB.
+#[derive(Clone, PartialEq)]
+pub struct Section {
+    blocks: PalettedBlocks,
+}
+
+impl Section {
+    fn empty() -> Self {
+        Self {
+            blocks: PalettedBlocks::new(SECTION_VOLUME, BlockType::Air),
+        }
+    }
+
+    /// Gets the block at coordinates local to the section
+    pub fn get(&self, x: usize, y: usize, z: usize) -> BlockType {
+        self.blocks.get(Self::index(x, y, z))
+    }
+
+    /// Number of blocks in the section that are not air
+    pub fn non_air_count(&self) -> usize {
+        SECTION_VOLUME - self.blocks.count(BlockType::Air)
+    }
+
+    /// The section's blocks, indexed by `(y * 16 + z) * 16 + x`
+    pub fn blocks(&self) -> &PalettedBlocks {
+        &self.blocks
+    }
+
+    /// Bytes used by the section, including its palette and indices
+    pub fn memory_usage(&self) -> usize {
+        std::mem::size_of::<Self>() + self.blocks.heap_bytes()
+    }
+
+    /// Sets a block and returns the one it replaced
+    fn set(&mut self, x: usize, y: usize, z: usize, block: BlockType) -> BlockType {
+        self.blocks.set(Self::index(x, y, z), block)
+    }
+
+    fn index(x: usize, y: usize, z: usize) -> usize {
+        (y * SECTION_SIZE + z) * SECTION_SIZE + x
+    }
+}
 
 /// Represents a chunk of voxel data
+///
+/// Blocks are stored in vertical sections of 16x16x16. Sections that only
+/// hold air are not allocated, so the empty sky and an unused deep
+/// underground cost almost nothing.
+///
+/// Every block change bumps the chunk's revision, so a save can tell which
+/// chunks changed since they were last written, loaded or generated.
+#[derive(Clone)]
 pub struct Chunk {
-    /// 3D array of blocks [x][y][z]
-    blocks: [[[BlockType; CHUNK_SIZE]; CHUNK_HEIGHT]; CHUNK_SIZE],
+    /// Sections from `MIN_Y` upwards; `None` is all air
+    sections: Vec<Option<Box<Section>>>,
     /// Position of the chunk in world coordinates
     pub position: (i32, i32),
+    /// Number of block changes made to the chunk
+    revision: u64,
+    /// Revision whose contents are already saved or can be regenerated
+    saved_revision: u64,
+}
+
+impl PartialEq for Chunk {
+    /// Chunks are equal when they hold the same blocks at the same position,
+    /// regardless of how they got there
+    fn eq(&self, other: &Self) -> bool {
+        self.position == other.position && self.sections == other.sections
+    }
+}
+
+impl std::fmt::Debug for Chunk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Chunk")
+            .field("position", &self.position)
+            .field("revision", &self.revision)
+            .field("allocated_sections", &self.allocated_sections())
+            .finish()
+    }
 }
 
 impl Chunk {
     /// Creates a new empty chunk filled with air
     pub fn new(position: (i32, i32)) -> Self {
         Self {
-            blocks: [[[BlockType::Air; CHUNK_SIZE]; CHUNK_HEIGHT]; CHUNK_SIZE],
+            sections: vec![None; SECTION_COUNT],
             position,
+            revision: 0,
+            saved_revision: 0,
         }
     }
 
-    /// Gets the block at the specified local coordinates
+    /// Gets the block at the specified local coordinates, with `y` from 0 up
+    /// to `MAX_Y`
     pub fn get_block(&self, x: usize, y: usize, z: usize) -> Option<BlockType> {
-        if x >= CHUNK_SIZE || y >= CHUNK_HEIGHT || z >= CHUNK_SIZE {
+        if y >= MAX_Y as usize {
             return None;
         }
-        Some(self.blocks[x][y][z])
+        self.get_block_at(x, y as i32, z)
     }
 
-    /// Sets the block at the specified local coordinates
+    /// Sets the block at the specified local coordinates, with `y` from 0 up
+    /// to `MAX_Y`
     pub fn set_block(&mut self, x: usize, y: usize, z: usize, block: BlockType) -> bool {
-        if x >= CHUNK_SIZE || y >= CHUNK_HEIGHT || z >= CHUNK_SIZE {
+        if y >= MAX_Y as usize {
             return false;
         }
-        self.blocks[x][y][z] = block;
+        self.set_block_at(x, y as i32, z, block)
+    }
+
+    /// Gets the block at local x and z and any layer from `MIN_Y` to `MAX_Y`
+    pub fn get_block_at(&self, x: usize, y: i32, z: usize) -> Option<BlockType> {
+        let (section, local_y) = Self::locate(x, y, z)?;
+        Some(match &self.sections[section] {
+            Some(section) => section.get(x, local_y, z),
+            None => BlockType::Air,
+        })
+    }
+
+    /// Sets the block at local x and z and any layer from `MIN_Y` to `MAX_Y`
+    ///
+    /// Allocates the section on the first non-air block and frees it again
+    /// once it only holds air.
+    pub fn set_block_at(&mut self, x: usize, y: i32, z: usize, block: BlockType) -> bool {
+        let Some((index, local_y)) = Self::locate(x, y, z) else { return false };
+        let slot = &mut self.sections[index];
+        let changed = match slot {
+            Some(section) => {
+                let old = section.set(x, local_y, z, block);
+                if section.non_air_count() == 0 {
+                    *slot = None;
+                }
+                old != block
+            }
+            None if block == BlockType::Air => false,
+            None => {
+                let mut section = Box::new(Section::empty());
+                section.set(x, local_y, z, block);
+                *slot = Some(section);
+                true
+            }
+        };
+        if changed {
+            self.revision += 1;
+        }
         true
     }
 
+    /// Number of block changes made to the chunk so far
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Returns true if blocks changed since the last [`Chunk::mark_saved`]
+    pub fn is_modified(&self) -> bool {
+        self.revision != self.saved_revision
+    }
+
+    /// Records that the chunk's contents as of `revision` are on disk, or
+    /// can be regenerated exactly
+    ///
+    /// Taking the revision rather than assuming the current one lets a save
+    /// made from a copy leave later edits flagged as modified.
+    pub fn mark_saved(&mut self, revision: u64) {
+        self.saved_revision = revision.min(self.revision);
+    }
+
+    /// The section at `index` counted from the bottom, if it holds any blocks
+    pub fn section(&self, index: usize) -> Option<&Section> {
+        self.sections.get(index)?.as_deref()
+    }
+
+    /// Lowest layer covered by the section at `index`
+    pub fn section_min_y(index: usize) -> i32 {
+        MIN_Y + (index * SECTION_SIZE) as i32
+    }
+
+    /// Number of sections that hold any blocks
+    pub fn allocated_sections(&self) -> usize {
+        self.sections.iter().filter(|section| section.is_some()).count()
+    }
+
+    /// Bytes used by the chunk and all of its allocated sections
+    pub fn memory_usage(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.sections.capacity() * std::mem::size_of::<Option<Box<Section>>>()
+            + self.sections.iter().flatten().map(|section| section.memory_usage()).sum::<usize>()
+    }
+
+    /// Layers spanned by the allocated sections, as (lowest, one past highest)
+    pub fn occupied_range(&self) -> Option<(i32, i32)> {
+        let first = self.sections.iter().position(Option::is_some)?;
+        let last = self.sections.iter().rposition(Option::is_some)?;
+        Some((Self::section_min_y(first), Self::section_min_y(last + 1)))
+    }
+
+    /// Section index and layer within it for local coordinates
+    fn locate(x: usize, y: i32, z: usize) -> Option<(usize, usize)> {
+        if x >= CHUNK_SIZE || z >= CHUNK_SIZE || !(MIN_Y..MAX_Y).contains(&y) {
+            return None;
+        }
+        let offset = (y - MIN_Y) as usize;
+        Some((offset / SECTION_SIZE, offset % SECTION_SIZE))
+    }
+
     /// Generates terrain for this chunk using simple height map
     pub fn generate_terrain(&mut self, noise: &noise::Perlin) {
         use noise::NoiseFn;
@@ -57,7 +242,7 @@ impl Chunk {
                 
                 // Convert noise (-1 to 1) to height (10 to 30)
                 let height = ((noise_value + 1.0) * 10.0 + 10.0) as usize;
-                let height = height.min(CHUNK_HEIGHT - 1);
+                let height = height.min(MAX_Y as usize - 1);
                 
                 // Fill blocks from bottom to height
                 for y in 0..=height {
@@ -78,6 +263,7 @@ impl Chunk {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
 
     #[test]
     fn test_chunk_creation() {
@@ -97,6 +283,119 @@ mod tests {
     fn test_out_of_bounds() {
         let chunk = Chunk::new((0, 0));
         assert_eq!(chunk.get_block(CHUNK_SIZE, 0, 0), None);
-        assert_eq!(chunk.get_block(0, CHUNK_HEIGHT, 0), None);
+        assert_eq!(chunk.get_block(0, MAX_Y as usize, 0), None);
+        assert_eq!(chunk.get_block_at(0, MIN_Y - 1, 0), None);
+        assert_eq!(chunk.get_block_at(0, MAX_Y, 0), None);
+    }
+
+    #[test]
+    fn test_negative_y() {
+        let mut chunk = Chunk::new((0, 0));
+        assert_eq!(chunk.get_block_at(3, MIN_Y, 3), Some(BlockType::Air));
+        assert!(chunk.set_block_at(3, MIN_Y, 3, BlockType::Bedrock));
+        assert!(chunk.set_block_at(3, -1, 3, BlockType::Stone));
+        assert_eq!(chunk.get_block_at(3, MIN_Y, 3), Some(BlockType::Bedrock));
+        assert_eq!(chunk.get_block_at(3, -1, 3), Some(BlockType::Stone));
+        // Layer 0 is the same block through both accessors
+        chunk.set_block(3, 0, 3, BlockType::Dirt);
+        assert_eq!(chunk.get_block_at(3, 0, 3), Some(BlockType::Dirt));
+    }
+
+    #[test]
+    fn test_sections_are_allocated_on_demand() {
+        let mut chunk = Chunk::new((0, 0));
+        assert_eq!(chunk.allocated_sections(), 0);
+        assert_eq!(chunk.occupied_range(), None);
+
+        // Writing air never allocates
+        chunk.set_block(1, 100, 1, BlockType::Air);
+        assert_eq!(chunk.allocated_sections(), 0);
+
+        chunk.set_block(1, 100, 1, BlockType::Stone);
+        chunk.set_block(2, 101, 1, BlockType::Stone);
+        chunk.set_block_at(0, -20, 0, BlockType::Stone);
+        assert_eq!(chunk.allocated_sections(), 2);
+        assert_eq!(chunk.occupied_range(), Some((-32, 112)));
+
+        // Clearing the last block frees the section, so emptied chunks
+        // compare equal to fresh ones
+        chunk.set_block_at(0, -20, 0, BlockType::Air);
+        chunk.set_block(1, 100, 1, BlockType::Air);
+        chunk.set_block(2, 101, 1, BlockType::Air);
+        assert_eq!(chunk.allocated_sections(), 0);
+        assert!(chunk == Chunk::new((0, 0)));
+    }
+
+    #[test]
+    fn test_revision_tracks_changes() {
+        let mut chunk = Chunk::new((0, 0));
+        assert!(!chunk.is_modified());
+        // Writes that change nothing leave the revision alone
+        chunk.set_block(1, 1, 1, BlockType::Air);
+        assert_eq!(chunk.revision(), 0);
+
+        chunk.set_block(1, 1, 1, BlockType::Stone);
+        chunk.set_block(1, 1, 1, BlockType::Stone);
+        chunk.set_block_at(1, -5, 1, BlockType::Dirt);
+        assert_eq!(chunk.revision(), 2);
+        assert!(chunk.is_modified());
+
+        let saved = chunk.revision();
+        chunk.set_block(2, 1, 1, BlockType::Sand);
+        chunk.mark_saved(saved);
+        assert!(chunk.is_modified());
+        chunk.mark_saved(chunk.revision());
+        assert!(!chunk.is_modified());
+
+        // Edits back to the original blocks still count as changes, but
+        // equality only looks at the blocks
+        chunk.set_block(1, 1, 1, BlockType::Air);
+        chunk.set_block_at(1, -5, 1, BlockType::Air);
+        chunk.set_block(2, 1, 1, BlockType::Air);
+        assert!(chunk.is_modified());
+        assert!(chunk == Chunk::new((0, 0)));
+    }
+
+    proptest! {
+        #[test]
+        fn prop_matches_dense_array(
+            writes in proptest::collection::vec(
+                (
+                    0..CHUNK_SIZE,
+                    MIN_Y..MAX_Y,
+                    0..CHUNK_SIZE,
+                    proptest::sample::select(BlockType::ALL),
+                ),
+                0..1000,
+            ),
+        ) {
+            let mut chunk = Chunk::new((0, 0));
+            let height = (MAX_Y - MIN_Y) as usize;
+            let mut dense = vec![BlockType::Air; CHUNK_SIZE * height * CHUNK_SIZE];
+            let index = |x: usize, y: i32, z: usize| {
+                (x * height + (y - MIN_Y) as usize) * CHUNK_SIZE + z
+            };
+            for &(x, y, z, block) in &writes {
+                prop_assert!(chunk.set_block_at(x, y, z, block));
+                dense[index(x, y, z)] = block;
+            }
+            // Each written block and the ones just above and below it
+            for &(x, y, z, _) in &writes {
+                for y in (y - 1).max(MIN_Y)..(y + 2).min(MAX_Y) {
+                    prop_assert_eq!(chunk.get_block_at(x, y, z), Some(dense[index(x, y, z)]));
+                }
+            }
+            let occupied = (0..SECTION_COUNT)
+                .filter(|&section| {
+                    let min_y = Chunk::section_min_y(section);
+                    (min_y..min_y + SECTION_SIZE as i32).any(|y| {
+                        (0..CHUNK_SIZE).any(|x| {
+                            (0..CHUNK_SIZE).any(|z| dense[index(x, y, z)] != BlockType::Air)
+                        })
+                    })
+                })
+                .count();
+            prop_assert_eq!(chunk.allocated_sections(), occupied);
+        }
     }
 }
@@ -0,0 +1,403 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+
+use crate::chunk::Chunk;
+use crate::mesh::{mesh_chunk, ChunkMesh, ChunkNeighbors, MeshingMode};
+use crate::worldgen::WorldGenerator;
+
+/// Owned copy of a chunk and its neighbours, so meshing can run off-thread
+pub struct MeshInput {
+    pub chunk: Chunk,
+    pub east: Option<Chunk>,
+    pub west: Option<Chunk>,
+    pub south: Option<Chunk>,
+    pub north: Option<Chunk>,
+    pub mode: MeshingMode,
+}
+
+impl MeshInput {
+    /// Copies a chunk and the neighbours it needs for border culling
+    pub fn new(chunk: &Chunk, neighbors: &ChunkNeighbors, mode: MeshingMode) -> Self {
+        Self {
+            chunk: chunk.clone(),
+            east: neighbors.east.cloned(),
+            west: neighbors.west.cloned(),
+            south: neighbors.south.cloned(),
+            north: neighbors.north.cloned(),
+            mode,
+        }
+    }
+
+    fn build(&self) -> ChunkMesh {
+        let neighbors = ChunkNeighbors {
+            east: self.east.as_ref(),
+            west: self.west.as_ref(),
+            south: self.south.as_ref(),
+            north: self.north.as_ref(),
+        };
+        mesh_chunk(&self.chunk, &neighbors, self.mode)
+    }
+}
+
+/// Work that can be run on a worker thread
+pub enum Job {
+    /// Generate terrain for a new chunk
+    Generate { position: (i32, i32) },
+    /// Build the mesh for a chunk
+    Mesh(Box<MeshInput>),
+}
+
+impl Job {
+    /// Chunk coordinates the job works on
+    pub fn position(&self) -> (i32, i32) {
+        match self {
+            Job::Generate { position } => *position,
+            Job::Mesh(input) => input.chunk.position,
+        }
+    }
+}
+
+/// Output of a finished job
+pub enum JobResult {
+    Generated(Box<Chunk>),
+    Meshed {
+        position: (i32, i32),
+        mesh: ChunkMesh,
+    },
+}
+
+impl JobResult {
+    /// Chunk coordinates the result belongs to
+    pub fn position(&self) -> (i32, i32) {
+        match self {
+            JobResult::Generated(chunk) => chunk.position,
+            JobResult::Meshed { position, .. } => *position,
+        }
+    }
+}
+
+/// A job waiting in the queue, ordered by priority then submission order
+struct QueuedJob {
+    priority: i32,
+    sequence: u64,
+    epoch: u64,
+    job: Job,
+}
+
+impl QueuedJob {
+    fn key(&self) -> Reverse<(i32, u64)> {
+        Reverse((self.priority, self.sequence))
+    }
+}
+
+impl PartialEq for QueuedJob {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for QueuedJob {}
+
+impl PartialOrd for QueuedJob {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for QueuedJob {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+#[derive(Default)]
+struct QueueState {
+    jobs: BinaryHeap<QueuedJob>,
+    /// Bumped by `cancel`; jobs and results from an older epoch are dropped
+    epochs: HashMap<(i32, i32), u64>,
+    /// Number of jobs currently being run by workers
+    running: usize,
+    shutdown: bool,
+}
+
+impl QueueState {
+    fn epoch(&self, position: (i32, i32)) -> u64 {
+        self.epochs.get(&position).copied().unwrap_or(0)
+    }
+}
+
+struct Shared {
+    state: Mutex<QueueState>,
+    /// Signalled when a job is queued or the pool shuts down
+    job_available: Condvar,
+    /// Signalled when a worker finishes a job
+    job_finished: Condvar,
+}
+
+/// Fixed set of worker threads running chunk generation and meshing
+///
+/// Jobs with the lowest priority value run first, so callers typically pass
+/// the squared distance to the camera.
+pub struct WorkerPool {
+    shared: Arc<Shared>,
+    results: Receiver<JobResult>,
+    workers: Vec<JoinHandle<()>>,
+    next_sequence: u64,
+}
+
+impl WorkerPool {
+    /// Spawns `threads` workers (at least one) generating chunks with `generator`
+    pub fn new(threads: usize, generator: Arc<dyn WorldGenerator>) -> Self {
+        Self::spawn(threads.max(1), generator)
+    }
+
+    fn spawn(threads: usize, generator: Arc<dyn WorldGenerator>) -> Self {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(QueueState::default()),
+            job_available: Condvar::new(),
+            job_finished: Condvar::new(),
+        });
+        let (sender, results) = mpsc::channel();
+
+        let workers = (0..threads)
+            .map(|i| {
+                let shared = Arc::clone(&shared);
+                let sender = sender.clone();
+                let generator = Arc::clone(&generator);
+                std::thread::Builder::new()
+                    .name(format!("chunk-worker-{}", i))
+                    .spawn(move || worker_loop(&shared, &sender, generator.as_ref()))
+                    .expect("failed to spawn worker thread")
+            })
+            .collect();
+
+        Self {
+            shared,
+            results,
+            workers,
+            next_sequence: 0,
+        }
+    }
+
+    /// Number of worker threads
+    pub fn thread_count(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Queues a job; lower `priority` values run first
+    pub fn submit(&mut self, job: Job, priority: i32) {
+        let mut state = self.shared.state.lock().unwrap();
+        let epoch = state.epoch(job.position());
+        state.jobs.push(QueuedJob {
+            priority,
+            sequence: self.next_sequence,
+            epoch,
+            job,
+        });
+        self.next_sequence += 1;
+        self.shared.job_available.notify_one();
+    }
+
+    /// Drops queued jobs for `position` and discards results of running ones
+    pub fn cancel(&mut self, position: (i32, i32)) {
+        let mut state = self.shared.state.lock().unwrap();
+        *state.epochs.entry(position).or_insert(0) += 1;
+        state.jobs.retain(|queued| queued.job.position() != position);
+    }
+
+    /// Recomputes the priority of every queued job, e.g. after the camera moved
+    pub fn reprioritize(&mut self, priority: impl Fn((i32, i32)) -> i32) {
+        let mut state = self.shared.state.lock().unwrap();
+        let jobs = std::mem::take(&mut state.jobs);
+        state.jobs = jobs
+            .into_iter()
+            .map(|mut queued| {
+                queued.priority = priority(queued.job.position());
+                queued
+            })
+            .collect();
+    }
+
+    /// Returns a finished job without blocking
+    pub fn try_recv(&self) -> Option<JobResult> {
+        self.results.try_recv().ok()
+    }
+
+    /// Number of jobs that are queued or running
+    pub fn pending(&self) -> usize {
+        let state = self.shared.state.lock().unwrap();
+        state.jobs.len() + state.running
+    }
+
+    /// Blocks until every queued and running job has finished
+    pub fn wait_idle(&self) {
+        let mut state = self.shared.state.lock().unwrap();
+        while !state.jobs.is_empty() || state.running > 0 {
+            state = self.shared.job_finished.wait(state).unwrap();
+        }
+    }
+}
+
+impl Drop for WorkerPool {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().shutdown = true;
+        self.shared.job_available.notify_all();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn worker_loop(shared: &Shared, sender: &Sender<JobResult>, generator: &dyn WorldGenerator) {
+    loop {
+        let queued = {
+            let mut state = shared.state.lock().unwrap();
+            loop {
+                if state.shutdown {
+                    return;
+                }
+                if let Some(queued) = state.jobs.pop() {
+                    state.running += 1;
+                    break queued;
+                }
+                state = shared.job_available.wait(state).unwrap();
+            }
+        };
+
+        let position = queued.job.position();
+        let result = run_job(queued.job, generator);
+
+        let mut state = shared.state.lock().unwrap();
+        // Checked under the lock so a concurrent `cancel` is never missed
+        if state.epoch(position) == queued.epoch {
+            let _ = sender.send(result);
+        }
+        state.running -= 1;
+        shared.job_finished.notify_all();
+    }
+}
+
+fn run_job(job: Job, generator: &dyn WorldGenerator) -> JobResult {
+    match job {
+        Job::Generate { position } => {
+            let mut chunk = Chunk::new(position);
+            generator.generate_chunk(&mut chunk);
+            // Generated blocks can always be generated again, so only later
+            // edits need saving
+            chunk.mark_saved(chunk.revision());
+            JobResult::Generated(Box::new(chunk))
+        }
+        Job::Mesh(input) => JobResult::Meshed {
+            position: input.chunk.position,
+            mesh: input.build(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::World;
+    use crate::worldgen::{GeneratorSettings, TerrainGenerator};
+
+    fn generator(seed: u32) -> Arc<dyn WorldGenerator> {
+        Arc::new(TerrainGenerator::new(GeneratorSettings {
+            seed,
+            ..Default::default()
+        }))
+    }
+
+    fn collect(pool: &WorkerPool) -> Vec<JobResult> {
+        pool.wait_idle();
+        std::iter::from_fn(|| pool.try_recv()).collect()
+    }
+
+    /// Mesh vertex and index data as raw bytes, keyed by chunk position
+    type MeshBytes = ((i32, i32), Vec<u8>);
+
+    /// Generates and meshes a 5x5 area with the given number of threads
+    fn generate_and_mesh(threads: usize) -> (Vec<Chunk>, Vec<MeshBytes>) {
+        let mut pool = WorkerPool::new(threads, generator(9));
+        for x in -2..=2 {
+            for z in -2..=2 {
+                pool.submit(Job::Generate { position: (x, z) }, x * x + z * z);
+            }
+        }
+
+        let mut world = World::new();
+        let mut chunks = Vec::new();
+        for result in collect(&pool) {
+            if let JobResult::Generated(chunk) = result {
+                chunks.push((*chunk).clone());
+                world.insert_chunk(*chunk);
+            }
+        }
+        chunks.sort_by_key(|chunk| chunk.position);
+
+        for position in world.take_dirty() {
+            let chunk = world.get_chunk(position).unwrap();
+            let input = MeshInput::new(chunk, &world.neighbors(position), MeshingMode::Greedy);
+            pool.submit(Job::Mesh(Box::new(input)), 0);
+        }
+        let mut meshes: Vec<_> = collect(&pool)
+            .into_iter()
+            .filter_map(|result| match result {
+                JobResult::Meshed { position, mesh } => {
+                    let mut bytes: Vec<u8> = bytemuck::cast_slice(&mesh.vertices).to_vec();
+                    bytes.extend_from_slice(bytemuck::cast_slice(&mesh.indices));
+                    Some((position, bytes))
+                }
+                JobResult::Generated(_) => None,
+            })
+            .collect();
+        meshes.sort_by_key(|(position, _)| *position);
+        (chunks, meshes)
+    }
+
+    #[test]
+    fn test_results_independent_of_thread_count() {
+        let (chunks_1, meshes_1) = generate_and_mesh(1);
+        let (chunks_4, meshes_4) = generate_and_mesh(4);
+        assert_eq!(chunks_1.len(), 25);
+        assert_eq!(meshes_1.len(), 25);
+        assert!(chunks_1 == chunks_4);
+        assert_eq!(meshes_1, meshes_4);
+    }
+
+    #[test]
+    fn test_jobs_dequeued_by_priority() {
+        // No workers, so the queue can be inspected directly
+        let mut pool = WorkerPool::spawn(0, generator(1));
+        for (position, priority) in [((5, 0), 25), ((1, 0), 1), ((3, 0), 9), ((2, 0), 1)] {
+            pool.submit(Job::Generate { position }, priority);
+        }
+        let order = |pool: &WorkerPool| {
+            let state = pool.shared.state.lock().unwrap();
+            let mut jobs: Vec<&QueuedJob> = state.jobs.iter().collect();
+            jobs.sort_by(|a, b| b.cmp(a));
+            jobs.iter().map(|queued| queued.job.position()).collect::<Vec<_>>()
+        };
+        // Equal priorities keep submission order
+        assert_eq!(order(&pool), vec![(1, 0), (2, 0), (3, 0), (5, 0)]);
+
+        pool.reprioritize(|(x, _)| -x);
+        assert_eq!(order(&pool), vec![(5, 0), (3, 0), (2, 0), (1, 0)]);
+    }
+
+    #[test]
+    fn test_cancelled_jobs_produce_no_results() {
+        let mut pool = WorkerPool::new(2, generator(3));
+        for x in 0..20 {
+            pool.submit(Job::Generate { position: (x, 0) }, x);
+        }
+        for x in (0..20).step_by(2) {
+            pool.cancel((x, 0));
+        }
+        let results = collect(&pool);
+        assert!(results.iter().all(|result| result.position().0 % 2 == 1));
+        assert_eq!(pool.pending(), 0);
+    }
+}
@@ -1,10 +1,26 @@
 pub mod block;
 pub mod chunk;
 pub mod camera;
+pub mod jobs;
+pub mod mesh;
+pub mod palette;
 pub mod renderer;
+pub mod save;
+pub mod streaming;
+pub mod vox;
+pub mod world;
+pub mod worldgen;
 
 // Re-export commonly used types
 pub use block::BlockType;
-pub use chunk::{Chunk, CHUNK_SIZE, CHUNK_HEIGHT};
+pub use chunk::{Chunk, Section, CHUNK_SIZE, MAX_Y, MIN_Y, SECTION_COUNT, SECTION_SIZE};
 pub use camera::Camera;
+pub use mesh::{
+    build_chunk_mesh, build_greedy_mesh, mesh_chunk, ChunkMesh, ChunkNeighbors, MeshingMode,
+};
+pub use palette::PalettedBlocks;
 pub use renderer::Renderer;
+pub use save::{SaveError, SaveManager, WorldMetadata, WorldSave};
+pub use vox::{VoxError, VoxFile};
+pub use world::World;
+pub use worldgen::{GeneratorSettings, TerrainGenerator, WorldGenerator};
@@ -1,4 +1,8 @@
-use voxel_game::{Camera, Renderer};
+use voxel_game::streaming::{ChunkStreamer, StreamingConfig};
+use voxel_game::{
+    Camera, GeneratorSettings, Renderer, SaveManager, TerrainGenerator, World, WorldMetadata,
+    WorldSave,
+};
 use winit::{
     event::*,
     event_loop::EventLoop,
@@ -6,18 +10,39 @@ use winit::{
     window::WindowBuilder,
 };
 use std::sync::Arc;
+use std::time::Duration;
+
+/// Directory the world is saved in unless another is given
+const DEFAULT_SAVE_DIR: &str = "world";
+/// Time between autosaves of edited chunks
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(30);
 
 struct GameState {
     camera: Camera,
+    world: World,
+    streamer: ChunkStreamer,
+    terrain: Arc<TerrainGenerator>,
+    saves: SaveManager,
     movement_speed: f32,
     rotation_speed: f32,
     last_frame_time: std::time::Instant,
 }
 
 impl GameState {
-    fn new(aspect_ratio: f32) -> Self {
+    fn new(aspect_ratio: f32, save: WorldSave, metadata: &WorldMetadata) -> Self {
+        let saves = SaveManager::new(save, AUTOSAVE_INTERVAL);
+        let generator = Arc::new(TerrainGenerator::new(metadata.generator_settings()));
+        let streamer =
+            ChunkStreamer::new(StreamingConfig::default(), saves.generator(generator.clone()));
+        let mut camera = Camera::new(aspect_ratio);
+        let [x, y, z] = metadata.spawn;
+        camera.position = glam::Vec3::new(x as f32, y as f32, z as f32);
         Self {
-            camera: Camera::new(aspect_ratio),
+            camera,
+            world: World::new(),
+            streamer,
+            terrain: generator,
+            saves,
             movement_speed: 10.0,
             rotation_speed: 0.002,
             last_frame_time: std::time::Instant::now(),
@@ -30,6 +55,59 @@ impl GameState {
         self.last_frame_time = current_time;
         delta_time
     }
+
+    /// Streams chunks around the camera and hands the results to the renderer
+    fn stream_chunks(&mut self, renderer: &mut Renderer) {
+        let update = self.streamer.update(&mut self.world, self.camera.position);
+        for position in update.unloaded {
+            renderer.remove_chunk(position);
+        }
+        for (position, mesh) in update.meshes {
+            renderer.upload_chunk_mesh(position, &mesh.vertices, &mesh.indices);
+        }
+        for (position, e) in self.terrain.take_erosion_errors() {
+            eprintln!("Ignoring erosion region {:?}: {}", position, e);
+        }
+        self.saves.save_chunks(update.evicted);
+        if let Err(e) = self.saves.update(&mut self.world) {
+            eprintln!("Save error: {}", e);
+        }
+    }
+
+    /// Writes every unsaved change before the game exits
+    fn save_all(&mut self) {
+        if let Err(e) = self.saves.flush(&mut self.world) {
+            eprintln!("Failed to save the world: {}", e);
+        }
+    }
+}
+
+/// Loads generator settings from the TOML file given as the first argument,
+/// or uses the defaults
+fn load_settings() -> GeneratorSettings {
+    let Some(path) = std::env::args().nth(1) else {
+        return GeneratorSettings::default();
+    };
+    match GeneratorSettings::load(&path) {
+        Ok(settings) => settings,
+        Err(e) => {
+            eprintln!("Failed to load {}: {}; using default settings", path, e);
+            GeneratorSettings::default()
+        }
+    }
+}
+
+/// Opens the save directory given as the second argument, creating a new
+/// world from `settings` if there is none yet
+fn open_save(settings: GeneratorSettings) -> (WorldSave, WorldMetadata) {
+    let dir = std::env::args().nth(2).unwrap_or_else(|| DEFAULT_SAVE_DIR.to_string());
+    if std::path::Path::new(&dir).join(voxel_game::save::METADATA_FILE).exists() {
+        return WorldSave::open(&dir)
+            .unwrap_or_else(|e| panic!("failed to open the world in {}: {}", dir, e));
+    }
+    let metadata = WorldMetadata::new(settings, [0, 32, 0]);
+    let save = WorldSave::create(&dir, &metadata).expect("failed to create the world save");
+    (save, metadata)
 }
 
 fn main() {
@@ -45,7 +123,8 @@ fn main() {
     let mut renderer = pollster::block_on(Renderer::new(window.clone()));
     let size = renderer.size();
     let aspect_ratio = size.width as f32 / size.height as f32;
-    let mut game_state = GameState::new(aspect_ratio);
+    let (save, metadata) = open_save(load_settings());
+    let mut game_state = GameState::new(aspect_ratio, save, &metadata);
 
     let mut keys_pressed = std::collections::HashSet::new();
 
@@ -123,6 +202,8 @@ fn main() {
                         game_state.camera.rotate(0.0, -rot_speed);
                     }
 
+                    game_state.stream_chunks(&mut renderer);
+
                     // Update camera uniform
                     renderer.update_camera(&game_state.camera);
 
@@ -139,6 +220,7 @@ fn main() {
             Event::AboutToWait => {
                 window.request_redraw();
             }
+            Event::LoopExiting => game_state.save_all(),
             _ => {}
         }
     }).unwrap();
@@ -0,0 +1,566 @@
+use crate::block::BlockType;
+use crate::chunk::{Chunk, CHUNK_SIZE, MAX_Y, MIN_Y};
+use crate::renderer::Vertex;
+
+/// The six faces of a block, named after the direction of their normal
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Face {
+    PosX,
+    NegX,
+    PosY,
+    NegY,
+    PosZ,
+    NegZ,
+}
+
+impl Face {
+    pub const ALL: [Face; 6] = [
+        Face::PosX,
+        Face::NegX,
+        Face::PosY,
+        Face::NegY,
+        Face::PosZ,
+        Face::NegZ,
+    ];
+
+    /// Offset to the neighbouring block this face looks at
+    pub fn normal(&self) -> [i32; 3] {
+        match self {
+            Face::PosX => [1, 0, 0],
+            Face::NegX => [-1, 0, 0],
+            Face::PosY => [0, 1, 0],
+            Face::NegY => [0, -1, 0],
+            Face::PosZ => [0, 0, 1],
+            Face::NegZ => [0, 0, -1],
+        }
+    }
+
+    /// Axes spanning the face as (normal, u, v), chosen so that u x v points
+    /// along the normal and quads wind counter-clockwise seen from outside
+    pub(crate) fn axes(&self) -> (usize, usize, usize) {
+        match self {
+            Face::PosX => (0, 1, 2),
+            Face::NegX => (0, 2, 1),
+            Face::PosY => (1, 2, 0),
+            Face::NegY => (1, 0, 2),
+            Face::PosZ => (2, 0, 1),
+            Face::NegZ => (2, 1, 0),
+        }
+    }
+
+    fn is_positive(&self) -> bool {
+        matches!(self, Face::PosX | Face::PosY | Face::PosZ)
+    }
+
+    /// Simple directional shading so neighbouring faces are distinguishable
+    fn shade(&self) -> f32 {
+        match self {
+            Face::PosY => 1.0,
+            Face::NegY => 0.5,
+            Face::PosX | Face::NegX => 0.8,
+            Face::PosZ | Face::NegZ => 0.65,
+        }
+    }
+}
+
+/// CPU-side geometry for a single chunk, in chunk-local x and z and world y
+#[derive(Debug, Clone, Default)]
+pub struct ChunkMesh {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+}
+
+impl ChunkMesh {
+    /// Creates an empty mesh
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns true if the mesh has no geometry
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    /// Number of quads in the mesh
+    pub fn quad_count(&self) -> usize {
+        self.vertices.len() / 4
+    }
+
+    /// Appends a quad for `face` whose minimum corner is the block at `block`,
+    /// spanning `width` blocks along the face's u axis and `height` along v
+    pub(crate) fn push_quad(
+        &mut self,
+        face: Face,
+        block: [i32; 3],
+        width: usize,
+        height: usize,
+        block_type: BlockType,
+    ) {
+        let (n, u, v) = face.axes();
+        let mut origin = [block[0] as f32, block[1] as f32, block[2] as f32];
+        if face.is_positive() {
+            origin[n] += 1.0;
+        }
+
+        let mut du = [0.0; 3];
+        du[u] = width as f32;
+        let mut dv = [0.0; 3];
+        dv[v] = height as f32;
+
+        let shade = face.shade();
+        let base = block_type.color();
+        let color = [base[0] * shade, base[1] * shade, base[2] * shade];
+
+        let start = self.vertices.len() as u32;
+        for (su, sv) in [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)] {
+            let position = [
+                origin[0] + du[0] * su + dv[0] * sv,
+                origin[1] + du[1] * su + dv[1] * sv,
+                origin[2] + du[2] * su + dv[2] * sv,
+            ];
+            self.vertices.push(Vertex { position, color });
+        }
+        self.indices
+            .extend_from_slice(&[start, start + 1, start + 2, start + 2, start + 3, start]);
+    }
+}
+
+/// Strategy used to turn chunk data into geometry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MeshingMode {
+    /// One quad per visible block face
+    Culled,
+    /// Coplanar visible faces of the same block type merged into larger quads
+    #[default]
+    Greedy,
+}
+
+/// The four chunks sharing a border with the chunk being meshed
+///
+/// A missing neighbour is treated as air, so faces on that border are drawn
+/// until the neighbour is loaded and the chunk is remeshed.
+#[derive(Clone, Copy, Default)]
+pub struct ChunkNeighbors<'a> {
+    /// Chunk at +X
+    pub east: Option<&'a Chunk>,
+    /// Chunk at -X
+    pub west: Option<&'a Chunk>,
+    /// Chunk at +Z
+    pub south: Option<&'a Chunk>,
+    /// Chunk at -Z
+    pub north: Option<&'a Chunk>,
+}
+
+/// Block lookup in chunk-local coordinates that reaches into neighbours
+///
+/// Only the layers holding allocated sections are meshed, so `y` counts up
+/// from `min_y`.
+struct MeshSource<'a> {
+    chunk: &'a Chunk,
+    neighbors: &'a ChunkNeighbors<'a>,
+    min_y: i32,
+}
+
+impl<'a> MeshSource<'a> {
+    /// Source covering the occupied layers of `chunk`, with their height,
+    /// or `None` if the chunk is empty
+    fn new(chunk: &'a Chunk, neighbors: &'a ChunkNeighbors<'a>) -> Option<(Self, usize)> {
+        let (low, high) = chunk.occupied_range()?;
+        Some((Self { chunk, neighbors, min_y: low }, (high - low) as usize))
+    }
+
+    /// Position of a block in mesh coordinates
+    fn position(&self, pos: [usize; 3]) -> [i32; 3] {
+        [pos[0] as i32, pos[1] as i32 + self.min_y, pos[2] as i32]
+    }
+
+    fn block(&self, pos: [usize; 3]) -> BlockType {
+        self.chunk
+            .get_block_at(pos[0], pos[1] as i32 + self.min_y, pos[2])
+            .unwrap_or(BlockType::Air)
+    }
+
+    /// Block on the other side of `face` of the block at `pos`
+    fn neighbor(&self, pos: [usize; 3], face: Face) -> BlockType {
+        let [dx, dy, dz] = face.normal();
+        let [x, y, z] = self.position(pos);
+        let (x, y, z) = (x + dx, y + dy, z + dz);
+        let size = CHUNK_SIZE as i32;
+
+        if !(MIN_Y..MAX_Y).contains(&y) {
+            return BlockType::Air;
+        }
+
+        let (chunk, x, z) = if x < 0 {
+            (self.neighbors.west, x + size, z)
+        } else if x >= size {
+            (self.neighbors.east, x - size, z)
+        } else if z < 0 {
+            (self.neighbors.north, x, z + size)
+        } else if z >= size {
+            (self.neighbors.south, x, z - size)
+        } else {
+            (Some(self.chunk), x, z)
+        };
+
+        chunk
+            .and_then(|c| c.get_block_at(x as usize, y, z as usize))
+            .unwrap_or(BlockType::Air)
+    }
+}
+
+/// Returns true if a face of `block` facing `neighbor` should be drawn
+///
+/// Faces are visible when the neighbour is transparent, except between two
+/// blocks of the same type (e.g. the inside of a body of water).
+pub fn face_visible(block: BlockType, neighbor: BlockType) -> bool {
+    block != BlockType::Air && neighbor.is_transparent() && neighbor != block
+}
+
+/// Builds a mesh for the chunk, emitting one quad per visible block face
+///
+/// Faces on the chunk border are culled against `neighbors`.
+pub fn build_chunk_mesh(chunk: &Chunk, neighbors: &ChunkNeighbors) -> ChunkMesh {
+    let mut mesh = ChunkMesh::new();
+    let Some((source, height)) = MeshSource::new(chunk, neighbors) else { return mesh };
+
+    for x in 0..CHUNK_SIZE {
+        for y in 0..height {
+            for z in 0..CHUNK_SIZE {
+                let block = source.block([x, y, z]);
+                if block == BlockType::Air {
+                    continue;
+                }
+
+                for face in Face::ALL {
+                    if face_visible(block, source.neighbor([x, y, z], face)) {
+                        mesh.push_quad(face, source.position([x, y, z]), 1, 1, block);
+                    }
+                }
+            }
+        }
+    }
+
+    mesh
+}
+
+/// Builds a mesh for the chunk using the requested meshing strategy
+pub fn mesh_chunk(chunk: &Chunk, neighbors: &ChunkNeighbors, mode: MeshingMode) -> ChunkMesh {
+    match mode {
+        MeshingMode::Culled => build_chunk_mesh(chunk, neighbors),
+        MeshingMode::Greedy => build_greedy_mesh(chunk, neighbors),
+    }
+}
+
+/// Builds a mesh for the chunk, merging adjacent coplanar faces of the same
+/// block type into larger quads
+///
+/// Covers exactly the same visible surface as [`build_chunk_mesh`] with far
+/// fewer vertices on flat terrain.
+pub fn build_greedy_mesh(chunk: &Chunk, neighbors: &ChunkNeighbors) -> ChunkMesh {
+    let mut mesh = ChunkMesh::new();
+    let Some((source, height)) = MeshSource::new(chunk, neighbors) else { return mesh };
+    let dims = [CHUNK_SIZE, height, CHUNK_SIZE];
+
+    for face in Face::ALL {
+        let (n, u, v) = face.axes();
+        let (size_u, size_v) = (dims[u], dims[v]);
+        let mut mask: Vec<Option<BlockType>> = vec![None; size_u * size_v];
+
+        for slice in 0..dims[n] {
+            // Mark every visible face in this slice with its block type
+            for j in 0..size_v {
+                for i in 0..size_u {
+                    let mut pos = [0; 3];
+                    pos[n] = slice;
+                    pos[u] = i;
+                    pos[v] = j;
+                    let block = source.block(pos);
+                    let visible = face_visible(block, source.neighbor(pos, face));
+                    mask[i + j * size_u] = visible.then_some(block);
+                }
+            }
+
+            // Grow rectangles along u first, then along v
+            for j in 0..size_v {
+                let mut i = 0;
+                while i < size_u {
+                    let Some(block) = mask[i + j * size_u] else {
+                        i += 1;
+                        continue;
+                    };
+
+                    let mut width = 1;
+                    while i + width < size_u && mask[i + width + j * size_u] == Some(block) {
+                        width += 1;
+                    }
+
+                    let mut height = 1;
+                    while j + height < size_v
+                        && (0..width).all(|k| mask[i + k + (j + height) * size_u] == Some(block))
+                    {
+                        height += 1;
+                    }
+
+                    for dj in 0..height {
+                        for k in 0..width {
+                            mask[i + k + (j + dj) * size_u] = None;
+                        }
+                    }
+
+                    let mut pos = [0; 3];
+                    pos[n] = slice;
+                    pos[u] = i;
+                    pos[v] = j;
+                    mesh.push_quad(face, source.position(pos), width, height, block);
+                    i += width;
+                }
+            }
+        }
+    }
+
+    mesh
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_empty_chunk_has_no_faces() {
+        let chunk = Chunk::new((0, 0));
+        let mesh = build_chunk_mesh(&chunk, &ChunkNeighbors::default());
+        assert!(mesh.is_empty());
+        assert!(mesh.vertices.is_empty());
+    }
+
+    #[test]
+    fn test_single_block_has_six_faces() {
+        let mut chunk = Chunk::new((0, 0));
+        chunk.set_block(4, 4, 4, BlockType::Stone);
+        let mesh = build_chunk_mesh(&chunk, &ChunkNeighbors::default());
+        assert_eq!(mesh.quad_count(), 6);
+        assert_eq!(mesh.vertices.len(), 24);
+        assert_eq!(mesh.indices.len(), 36);
+    }
+
+    #[test]
+    fn test_blocks_below_zero_are_meshed_at_their_height() {
+        let mut chunk = Chunk::new((0, 0));
+        chunk.set_block_at(2, MIN_Y, 2, BlockType::Stone);
+        chunk.set_block_at(2, -1, 2, BlockType::Stone);
+        chunk.set_block(2, 0, 2, BlockType::Stone);
+        for mode in [MeshingMode::Culled, MeshingMode::Greedy] {
+            let mesh = mesh_chunk(&chunk, &ChunkNeighbors::default(), mode);
+            let lowest = mesh.vertices.iter().map(|v| v.position[1]).fold(f32::MAX, f32::min);
+            let highest = mesh.vertices.iter().map(|v| v.position[1]).fold(f32::MIN, f32::max);
+            assert_eq!((lowest, highest), (MIN_Y as f32, 1.0));
+        }
+        // A lone block plus a two-block column crossing y = 0
+        assert_eq!(build_chunk_mesh(&chunk, &ChunkNeighbors::default()).quad_count(), 6 + 10);
+    }
+
+    #[test]
+    fn test_adjacent_blocks_cull_shared_faces() {
+        let mut chunk = Chunk::new((0, 0));
+        chunk.set_block(4, 4, 4, BlockType::Stone);
+        chunk.set_block(5, 4, 4, BlockType::Dirt);
+        assert_eq!(build_chunk_mesh(&chunk, &ChunkNeighbors::default()).quad_count(), 10);
+
+        // A solid 2x2x2 cube only shows its outer 24 faces
+        let mut chunk = Chunk::new((0, 0));
+        for x in 0..2 {
+            for y in 0..2 {
+                for z in 0..2 {
+                    chunk.set_block(x + 1, y + 1, z + 1, BlockType::Stone);
+                }
+            }
+        }
+        assert_eq!(build_chunk_mesh(&chunk, &ChunkNeighbors::default()).quad_count(), 24);
+    }
+
+    #[test]
+    fn test_transparent_neighbors() {
+        // Solid block under water still shows its top face
+        let mut chunk = Chunk::new((0, 0));
+        chunk.set_block(4, 4, 4, BlockType::Stone);
+        chunk.set_block(4, 5, 4, BlockType::Water);
+        let mesh = build_chunk_mesh(&chunk, &ChunkNeighbors::default());
+        // 6 stone faces + 5 water faces (the one against stone is hidden)
+        assert_eq!(mesh.quad_count(), 11);
+
+        // Two water blocks don't draw the face between them
+        let mut chunk = Chunk::new((0, 0));
+        chunk.set_block(4, 4, 4, BlockType::Water);
+        chunk.set_block(4, 4, 5, BlockType::Water);
+        assert_eq!(build_chunk_mesh(&chunk, &ChunkNeighbors::default()).quad_count(), 10);
+    }
+
+    /// Expands every quad of a mesh into the unit faces it covers, keyed by
+    /// normal, cell position and colour
+    fn unit_faces(mesh: &ChunkMesh) -> HashSet<([i32; 3], [i32; 3], [u32; 3])> {
+        let mut faces = HashSet::new();
+        for quad in mesh.vertices.chunks(4) {
+            let p: Vec<[f32; 3]> = quad.iter().map(|v| v.position).collect();
+            let a = [p[1][0] - p[0][0], p[1][1] - p[0][1], p[1][2] - p[0][2]];
+            let b = [p[3][0] - p[0][0], p[3][1] - p[0][1], p[3][2] - p[0][2]];
+            let cross = [
+                a[1] * b[2] - a[2] * b[1],
+                a[2] * b[0] - a[0] * b[2],
+                a[0] * b[1] - a[1] * b[0],
+            ];
+            let normal = cross.map(|c| c.signum() as i32 * (c != 0.0) as i32);
+            let min = [0, 1, 2].map(|i| p.iter().map(|q| q[i]).fold(f32::MAX, f32::min) as i32);
+            let max = [0, 1, 2].map(|i| p.iter().map(|q| q[i]).fold(f32::MIN, f32::max) as i32);
+            let color = quad[0].color.map(f32::to_bits);
+            for x in min[0]..max[0].max(min[0] + 1) {
+                for y in min[1]..max[1].max(min[1] + 1) {
+                    for z in min[2]..max[2].max(min[2] + 1) {
+                        assert!(faces.insert((normal, [x, y, z], color)), "overlapping quads");
+                    }
+                }
+            }
+        }
+        faces
+    }
+
+    fn assert_same_surface(chunk: &Chunk) {
+        let culled = build_chunk_mesh(chunk, &ChunkNeighbors::default());
+        let greedy = build_greedy_mesh(chunk, &ChunkNeighbors::default());
+        assert!(greedy.quad_count() <= culled.quad_count());
+        assert_eq!(unit_faces(&culled), unit_faces(&greedy));
+    }
+
+    #[test]
+    fn test_greedy_merges_flat_slab() {
+        let mut chunk = Chunk::new((0, 0));
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                chunk.set_block(x, 0, z, BlockType::Stone);
+            }
+        }
+        assert_eq!(build_greedy_mesh(&chunk, &ChunkNeighbors::default()).quad_count(), 6);
+        assert_same_surface(&chunk);
+    }
+
+    #[test]
+    fn test_greedy_keeps_block_types_apart() {
+        let mut chunk = Chunk::new((0, 0));
+        for x in 0..4 {
+            chunk.set_block(x, 0, 0, BlockType::Stone);
+            chunk.set_block(x + 4, 0, 0, BlockType::Dirt);
+        }
+        let mesh = build_greedy_mesh(&chunk, &ChunkNeighbors::default());
+        // Each run gets 4 long sides and one end cap
+        assert_eq!(mesh.quad_count(), 10);
+        assert_same_surface(&chunk);
+    }
+
+    #[test]
+    fn test_greedy_matches_culled_surface() {
+        let mut chunk = Chunk::new((3, -2));
+        chunk.generate_terrain(&noise::Perlin::new(7));
+        assert_same_surface(&chunk);
+
+        // Scattered blocks of mixed types, including water
+        let mut chunk = Chunk::new((0, 0));
+        let mut state = 12345u32;
+        for x in 0..CHUNK_SIZE {
+            for y in 0..24 {
+                for z in 0..CHUNK_SIZE {
+                    state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+                    let block = match state >> 29 {
+                        0 | 1 => BlockType::Stone,
+                        2 => BlockType::Dirt,
+                        3 => BlockType::Water,
+                        _ => BlockType::Air,
+                    };
+                    chunk.set_block(x, y, z, block);
+                }
+            }
+        }
+        assert_same_surface(&chunk);
+    }
+
+    #[test]
+    fn test_border_faces_culled_against_neighbors() {
+        let mut chunk = Chunk::new((0, 0));
+        let mut east = Chunk::new((1, 0));
+        chunk.set_block(CHUNK_SIZE - 1, 4, 4, BlockType::Stone);
+        east.set_block(0, 4, 4, BlockType::Stone);
+
+        let alone = build_chunk_mesh(&chunk, &ChunkNeighbors::default());
+        assert_eq!(alone.quad_count(), 6);
+
+        let neighbors = ChunkNeighbors {
+            east: Some(&east),
+            ..Default::default()
+        };
+        for mode in [MeshingMode::Culled, MeshingMode::Greedy] {
+            assert_eq!(mesh_chunk(&chunk, &neighbors, mode).quad_count(), 5);
+        }
+
+        // A transparent block across the border keeps the face
+        east.set_block(0, 4, 4, BlockType::Water);
+        let neighbors = ChunkNeighbors {
+            east: Some(&east),
+            ..Default::default()
+        };
+        assert_eq!(build_chunk_mesh(&chunk, &neighbors).quad_count(), 6);
+    }
+
+    #[test]
+    fn test_terrain_seams_are_closed() {
+        let perlin = noise::Perlin::new(3);
+        let chunks: Vec<Chunk> = [(0, 0), (1, 0), (-1, 0), (0, 1), (0, -1)]
+            .into_iter()
+            .map(|pos| {
+                let mut chunk = Chunk::new(pos);
+                chunk.generate_terrain(&perlin);
+                chunk
+            })
+            .collect();
+        let neighbors = ChunkNeighbors {
+            east: Some(&chunks[1]),
+            west: Some(&chunks[2]),
+            south: Some(&chunks[3]),
+            north: Some(&chunks[4]),
+        };
+
+        // With all neighbours solid below the surface, no side face at the
+        // border may sit lower than the neighbouring column's surface
+        let mesh = build_chunk_mesh(&chunks[0], &neighbors);
+        let isolated = build_chunk_mesh(&chunks[0], &ChunkNeighbors::default());
+        assert!(mesh.quad_count() < isolated.quad_count());
+        for quad in mesh.vertices.chunks(4) {
+            let x = quad.iter().map(|v| v.position[0]).fold(f32::MAX, f32::min);
+            let y = quad.iter().map(|v| v.position[1]).fold(f32::MAX, f32::min);
+            if quad.iter().all(|v| v.position[0] == 0.0) {
+                let z = quad.iter().map(|v| v.position[2]).fold(f32::MAX, f32::min);
+                let west_top = (0..MAX_Y as usize)
+                    .rev()
+                    .find(|&h| chunks[2].get_block(CHUNK_SIZE - 1, h, z as usize) != Some(BlockType::Air))
+                    .unwrap();
+                assert!(y as usize > west_top, "hidden border face at x={x} y={y} z={z}");
+            }
+        }
+        assert_eq!(
+            unit_faces(&mesh),
+            unit_faces(&build_greedy_mesh(&chunks[0], &neighbors))
+        );
+    }
+
+    #[test]
+    fn test_face_colors_and_indices() {
+        let mut chunk = Chunk::new((0, 0));
+        chunk.set_block(0, 0, 0, BlockType::Grass);
+        let mesh = build_chunk_mesh(&chunk, &ChunkNeighbors::default());
+        let top = BlockType::Grass.color();
+        assert!(mesh.vertices.iter().any(|v| v.color == top));
+        assert!(mesh
+            .indices
+            .iter()
+            .all(|&i| (i as usize) < mesh.vertices.len()));
+    }
+}
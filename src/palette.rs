@@ -0,0 +1,287 @@
+use crate::block::BlockType;
+
+/// Block storage with a palette of the distinct blocks and bit-packed
+/// indices into it
+///
+/// A run of identical blocks needs no indices at all. When the palette
+/// outgrows the index width every index is repacked one bit wider, and once
+/// enough block types disappear the palette is compacted and the indices
+/// narrowed again. Indices never straddle two words, so a lookup is a single
+/// shift and mask.
+#[derive(Clone)]
+pub struct PalettedBlocks {
+    len: usize,
+    palette: Vec<BlockType>,
+    /// How many slots use each palette entry; entries at 0 are free for reuse
+    counts: Vec<u32>,
+    bits: u32,
+    words: Vec<u64>,
+}
+
+impl PalettedBlocks {
+    /// Storage for `len` blocks, all set to `block`
+    pub fn new(len: usize, block: BlockType) -> Self {
+        Self {
+            len,
+            palette: vec![block],
+            counts: vec![len as u32],
+            bits: 0,
+            words: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Gets the block in slot `index`
+    pub fn get(&self, index: usize) -> BlockType {
+        self.palette[self.entry(index)]
+    }
+
+    /// Sets the block in slot `index` and returns the block it replaced
+    pub fn set(&mut self, index: usize, block: BlockType) -> BlockType {
+        let old_entry = self.entry(index);
+        let old = self.palette[old_entry];
+        if old == block {
+            return old;
+        }
+        let entry = self.entry_for(block);
+        write(&mut self.words, self.bits, index, entry);
+        self.counts[entry] += 1;
+        self.counts[old_entry] -= 1;
+        if self.counts[old_entry] == 0 {
+            self.shrink();
+        }
+        old
+    }
+
+    /// Iterates over the blocks in slot order
+    pub fn iter(&self) -> impl Iterator<Item = BlockType> + '_ {
+        (0..self.len).map(|index| self.get(index))
+    }
+
+    /// Number of slots holding `block`
+    pub fn count(&self, block: BlockType) -> usize {
+        self.palette
+            .iter()
+            .zip(&self.counts)
+            .filter(|&(&entry, _)| entry == block)
+            .map(|(_, &count)| count as usize)
+            .sum()
+    }
+
+    /// The distinct blocks currently stored, in palette order
+    pub fn palette(&self) -> impl Iterator<Item = BlockType> + '_ {
+        self.palette
+            .iter()
+            .zip(&self.counts)
+            .filter(|&(_, &count)| count > 0)
+            .map(|(&block, _)| block)
+    }
+
+    /// Width in bits of each packed index
+    pub fn bits_per_block(&self) -> u32 {
+        self.bits
+    }
+
+    /// Bytes allocated on the heap for the palette and the indices
+    pub fn heap_bytes(&self) -> usize {
+        self.palette.capacity() * std::mem::size_of::<BlockType>()
+            + self.counts.capacity() * std::mem::size_of::<u32>()
+            + self.words.capacity() * std::mem::size_of::<u64>()
+    }
+
+    fn entry(&self, index: usize) -> usize {
+        assert!(index < self.len, "slot {} out of {}", index, self.len);
+        if self.bits == 0 {
+            return 0;
+        }
+        let per_word = 64 / self.bits as usize;
+        let shift = (index % per_word) as u32 * self.bits;
+        ((self.words[index / per_word] >> shift) & mask(self.bits)) as usize
+    }
+
+    /// Palette entry for `block`, adding it and widening the indices if
+    /// needed
+    fn entry_for(&mut self, block: BlockType) -> usize {
+        if let Some(entry) = self.palette.iter().position(|&b| b == block) {
+            return entry;
+        }
+        if let Some(entry) = self.counts.iter().position(|&count| count == 0) {
+            self.palette[entry] = block;
+            return entry;
+        }
+        self.palette.push(block);
+        self.counts.push(0);
+        if self.palette.len() > 1 << self.bits {
+            self.repack(bits_for(self.palette.len()), |entry| entry);
+        }
+        self.palette.len() - 1
+    }
+
+    /// Drops unused entries once the indices could be two bits narrower, or
+    /// all of them once only one block is left
+    ///
+    /// Waiting for two bits keeps a single odd block being placed and removed
+    /// from repacking the whole storage every time.
+    fn shrink(&mut self) {
+        let used = self.counts.iter().filter(|&&count| count > 0).count();
+        let bits = bits_for(used);
+        if used > 1 && bits + 1 >= self.bits {
+            return;
+        }
+        let mut remap = vec![0; self.palette.len()];
+        let mut palette = Vec::with_capacity(used);
+        let mut counts = Vec::with_capacity(used);
+        for (entry, (&block, &count)) in self.palette.iter().zip(&self.counts).enumerate() {
+            if count > 0 {
+                remap[entry] = palette.len();
+                palette.push(block);
+                counts.push(count);
+            }
+        }
+        self.repack(bits, |entry| remap[entry]);
+        self.palette = palette;
+        self.counts = counts;
+    }
+
+    fn repack(&mut self, bits: u32, remap: impl Fn(usize) -> usize) {
+        let mut words = vec![0; word_count(self.len, bits)];
+        if bits > 0 {
+            for index in 0..self.len {
+                write(&mut words, bits, index, remap(self.entry(index)));
+            }
+        }
+        self.bits = bits;
+        self.words = words;
+    }
+}
+
+impl PartialEq for PalettedBlocks {
+    /// Equal when every slot holds the same block, however each side's
+    /// palette happens to be ordered
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.iter().eq(other.iter())
+    }
+}
+
+/// Index width needed to tell `entries` palette entries apart
+fn bits_for(entries: usize) -> u32 {
+    if entries <= 1 {
+        0
+    } else {
+        usize::BITS - (entries - 1).leading_zeros()
+    }
+}
+
+fn mask(bits: u32) -> u64 {
+    (1 << bits) - 1
+}
+
+fn word_count(len: usize, bits: u32) -> usize {
+    if bits == 0 {
+        0
+    } else {
+        len.div_ceil(64 / bits as usize)
+    }
+}
+
+fn write(words: &mut [u64], bits: u32, index: usize, entry: usize) {
+    if bits == 0 {
+        return;
+    }
+    let per_word = 64 / bits as usize;
+    let shift = (index % per_word) as u32 * bits;
+    let word = &mut words[index / per_word];
+    *word = (*word & !(mask(bits) << shift)) | ((entry as u64) << shift);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const LEN: usize = 4096;
+
+    fn block() -> impl Strategy<Value = BlockType> {
+        proptest::sample::select(BlockType::ALL)
+    }
+
+    #[test]
+    fn test_palette_grows_and_shrinks() {
+        let mut blocks = PalettedBlocks::new(LEN, BlockType::Air);
+        assert_eq!(blocks.bits_per_block(), 0);
+        assert!(blocks.heap_bytes() < 64);
+
+        for (i, &block) in BlockType::ALL.iter().enumerate() {
+            blocks.set(i * 7, block);
+        }
+        assert_eq!(blocks.palette().count(), BlockType::ALL.len());
+        assert_eq!(blocks.bits_per_block(), bits_for(BlockType::ALL.len()));
+        assert_eq!(blocks.count(BlockType::Air), LEN - BlockType::ALL.len() + 1);
+
+        // Clearing everything collapses back to a single entry
+        for i in 0..BlockType::ALL.len() {
+            assert_eq!(blocks.set(i * 7, BlockType::Air), BlockType::ALL[i]);
+        }
+        assert_eq!(blocks.bits_per_block(), 0);
+        assert_eq!(blocks.palette().collect::<Vec<_>>(), vec![BlockType::Air]);
+        assert!(blocks == PalettedBlocks::new(LEN, BlockType::Air));
+    }
+
+    #[test]
+    fn test_single_odd_block_does_not_repack() {
+        let mut blocks = PalettedBlocks::new(LEN, BlockType::Stone);
+        blocks.set(0, BlockType::Dirt);
+        assert_eq!(blocks.bits_per_block(), 1);
+        // Placing and breaking a third block type keeps the wider indices
+        blocks.set(1, BlockType::CoalOre);
+        assert_eq!(blocks.bits_per_block(), 2);
+        blocks.set(1, BlockType::Stone);
+        assert_eq!(blocks.bits_per_block(), 2);
+        assert_eq!(blocks.get(0), BlockType::Dirt);
+        assert_eq!(blocks.get(1), BlockType::Stone);
+    }
+
+    proptest! {
+        #[test]
+        fn prop_matches_dense_array(
+            fill in block(),
+            writes in proptest::collection::vec((0..LEN, block()), 0..2000),
+        ) {
+            let mut blocks = PalettedBlocks::new(LEN, fill);
+            let mut dense = vec![fill; LEN];
+            for (index, block) in writes {
+                prop_assert_eq!(blocks.set(index, block), dense[index]);
+                dense[index] = block;
+            }
+            prop_assert!(blocks.iter().eq(dense.iter().copied()));
+            for &block in BlockType::ALL {
+                let expected = dense.iter().filter(|&&b| b == block).count();
+                prop_assert_eq!(blocks.count(block), expected);
+            }
+            // Never more than two bits wider than the variety needs
+            let used = blocks.palette().count();
+            prop_assert!(blocks.bits_per_block() <= bits_for(used) + 1);
+        }
+
+        #[test]
+        fn prop_clustered_writes_match_dense_array(
+            writes in proptest::collection::vec((0..64usize, block()), 0..500),
+        ) {
+            // Few slots rewritten many times stress reuse of freed entries
+            let mut blocks = PalettedBlocks::new(LEN, BlockType::Air);
+            let mut dense = vec![BlockType::Air; LEN];
+            for (index, block) in writes {
+                blocks.set(index * 61, block);
+                dense[index * 61] = block;
+            }
+            prop_assert!(blocks.iter().eq(dense.iter().copied()));
+        }
+    }
+}
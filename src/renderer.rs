@@ -1,6 +1,8 @@
+use std::collections::HashMap;
 use wgpu::util::DeviceExt;
 use winit::window::Window;
 use crate::camera::Camera;
+use crate::chunk::CHUNK_SIZE;
 
 /// Vertex structure for rendering
 #[repr(C)]
@@ -52,22 +54,96 @@ impl CameraUniform {
     }
 }
 
+/// Uniform buffer holding the world-space offset of a chunk
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct ChunkUniform {
+    offset: [f32; 4],
+}
+
+/// GPU resources for one uploaded chunk mesh
+struct ChunkBuffers {
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    num_indices: u32,
+    offset_bind_group: wgpu::BindGroup,
+}
+
+/// Errors from headless renderer creation and frame readback
+#[derive(Debug)]
+pub enum RendererError {
+    /// No adapter (not even a software fallback) is available
+    NoAdapter,
+    /// The adapter refused to create a device
+    RequestDevice(wgpu::RequestDeviceError),
+    /// Pixel readback requires a renderer created with [`Renderer::new_headless`]
+    NotHeadless,
+    /// Mapping the readback buffer failed
+    BufferMap(wgpu::BufferAsyncError),
+    /// Encoding the PNG failed
+    Png(png::EncodingError),
+    Io(std::io::Error),
+}
+
+impl std::fmt::Display for RendererError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RendererError::NoAdapter => write!(f, "no suitable graphics adapter found"),
+            RendererError::RequestDevice(e) => write!(f, "failed to create device: {}", e),
+            RendererError::NotHeadless => write!(f, "renderer does not draw to an offscreen texture"),
+            RendererError::BufferMap(e) => write!(f, "failed to map readback buffer: {}", e),
+            RendererError::Png(e) => write!(f, "failed to encode PNG: {}", e),
+            RendererError::Io(e) => write!(f, "I/O error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for RendererError {}
+
+impl From<std::io::Error> for RendererError {
+    fn from(e: std::io::Error) -> Self {
+        RendererError::Io(e)
+    }
+}
+
+impl From<png::EncodingError> for RendererError {
+    fn from(e: png::EncodingError) -> Self {
+        RendererError::Png(e)
+    }
+}
+
+/// Where frames end up: a window surface or an offscreen texture
+enum RenderTarget {
+    Surface {
+        surface: wgpu::Surface<'static>,
+        config: wgpu::SurfaceConfiguration,
+    },
+    Offscreen {
+        texture: wgpu::Texture,
+        view: wgpu::TextureView,
+    },
+}
+
 /// Renderer for the voxel world
 pub struct Renderer {
-    surface: wgpu::Surface<'static>,
+    target: RenderTarget,
     device: wgpu::Device,
     queue: wgpu::Queue,
-    config: wgpu::SurfaceConfiguration,
     size: winit::dpi::PhysicalSize<u32>,
     render_pipeline: wgpu::RenderPipeline,
-    vertex_buffer: wgpu::Buffer,
-    index_buffer: wgpu::Buffer,
-    num_indices: u32,
+    chunk_bind_group_layout: wgpu::BindGroupLayout,
+    chunks: HashMap<(i32, i32), ChunkBuffers>,
     camera_buffer: wgpu::Buffer,
     camera_bind_group: wgpu::BindGroup,
+    depth_texture: wgpu::Texture,
+    depth_view: wgpu::TextureView,
 }
 
 impl Renderer {
+    pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+    /// Colour format of the offscreen texture used by headless renderers
+    pub const OFFSCREEN_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
     pub async fn new(window: std::sync::Arc<Window>) -> Self {
         let size = window.inner_size();
 
@@ -124,6 +200,68 @@ impl Renderer {
         };
         surface.configure(&device, &config);
 
+        Self::with_target(device, queue, RenderTarget::Surface { surface, config }, surface_format, size)
+    }
+
+    /// Creates a renderer without a window that draws into an offscreen texture
+    ///
+    /// Falls back to a software adapter (e.g. llvmpipe) when no hardware
+    /// adapter is available, so it works on display-less CI machines.
+    pub async fn new_headless(width: u32, height: u32) -> Result<Self, RendererError> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            ..Default::default()
+        });
+
+        let mut adapter = None;
+        for force_fallback_adapter in [false, true] {
+            adapter = instance
+                .request_adapter(&wgpu::RequestAdapterOptions {
+                    power_preference: wgpu::PowerPreference::default(),
+                    compatible_surface: None,
+                    force_fallback_adapter,
+                })
+                .await;
+            if adapter.is_some() {
+                break;
+            }
+        }
+        let adapter = adapter.ok_or(RendererError::NoAdapter)?;
+
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    label: None,
+                    required_features: wgpu::Features::empty(),
+                    required_limits: wgpu::Limits::downlevel_webgl2_defaults()
+                        .using_resolution(adapter.limits()),
+                },
+                None,
+            )
+            .await
+            .map_err(RendererError::RequestDevice)?;
+
+        let size = winit::dpi::PhysicalSize::new(width.max(1), height.max(1));
+        let (texture, view) = Self::create_offscreen_texture(&device, size);
+        Ok(Self::with_target(
+            device,
+            queue,
+            RenderTarget::Offscreen { texture, view },
+            Self::OFFSCREEN_FORMAT,
+            size,
+        ))
+    }
+
+    /// Builds the pipeline and GPU resources shared by windowed and headless renderers
+    fn with_target(
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        target: RenderTarget,
+        format: wgpu::TextureFormat,
+        size: winit::dpi::PhysicalSize<u32>,
+    ) -> Self {
+        let (depth_texture, depth_view) = Self::create_depth_texture(&device, size);
+
         // Create camera uniform buffer
         let camera_uniform = CameraUniform::new();
         let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
@@ -156,6 +294,21 @@ impl Renderer {
             label: Some("camera_bind_group"),
         });
 
+        let chunk_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+                label: Some("chunk_bind_group_layout"),
+            });
+
         // Create shader module
         let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("Shader"),
@@ -166,7 +319,7 @@ impl Renderer {
         let render_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("Render Pipeline Layout"),
-                bind_group_layouts: &[&camera_bind_group_layout],
+                bind_group_layouts: &[&camera_bind_group_layout, &chunk_bind_group_layout],
                 push_constant_ranges: &[],
             });
 
@@ -182,7 +335,7 @@ impl Renderer {
                 module: &shader,
                 entry_point: "fs_main",
                 targets: &[Some(wgpu::ColorTargetState {
-                    format: config.format,
+                    format,
                     blend: Some(wgpu::BlendState::REPLACE),
                     write_mask: wgpu::ColorWrites::ALL,
                 })],
@@ -196,7 +349,13 @@ impl Renderer {
                 unclipped_depth: false,
                 conservative: false,
             },
-            depth_stencil: None,
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Self::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
             multisample: wgpu::MultisampleState {
                 count: 1,
                 mask: !0,
@@ -205,91 +364,152 @@ impl Renderer {
             multiview: None,
         });
 
-        // Create a simple cube for demonstration
-        let vertices = Self::create_cube_vertices();
-        let indices = Self::create_cube_indices();
-
-        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Vertex Buffer"),
-            contents: bytemuck::cast_slice(&vertices),
-            usage: wgpu::BufferUsages::VERTEX,
-        });
-
-        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Index Buffer"),
-            contents: bytemuck::cast_slice(&indices),
-            usage: wgpu::BufferUsages::INDEX,
-        });
-
-        let num_indices = indices.len() as u32;
-
         Self {
-            surface,
+            target,
             device,
             queue,
-            config,
             size,
             render_pipeline,
-            vertex_buffer,
-            index_buffer,
-            num_indices,
+            chunk_bind_group_layout,
+            chunks: HashMap::new(),
             camera_buffer,
             camera_bind_group,
+            depth_texture,
+            depth_view,
+        }
+    }
+
+    /// Creates a depth texture matching the render target size
+    fn create_depth_texture(
+        device: &wgpu::Device,
+        size: winit::dpi::PhysicalSize<u32>,
+    ) -> (wgpu::Texture, wgpu::TextureView) {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Depth Texture"),
+            size: wgpu::Extent3d {
+                width: size.width.max(1),
+                height: size.height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::DEPTH_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        (texture, view)
+    }
+
+    /// Creates the colour texture headless renderers draw into
+    fn create_offscreen_texture(
+        device: &wgpu::Device,
+        size: winit::dpi::PhysicalSize<u32>,
+    ) -> (wgpu::Texture, wgpu::TextureView) {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Offscreen Texture"),
+            size: wgpu::Extent3d {
+                width: size.width,
+                height: size.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::OFFSCREEN_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        (texture, view)
+    }
+
+    /// Uploads the mesh for the chunk at `position`, replacing any previous mesh
+    ///
+    /// Vertices are in chunk-local coordinates; the chunk's world offset is
+    /// applied in the vertex shader.
+    pub fn upload_chunk_mesh(&mut self, position: (i32, i32), vertices: &[Vertex], indices: &[u32]) {
+        if indices.is_empty() {
+            self.remove_chunk(position);
+            return;
         }
+
+        let vertex_buffer = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Chunk Vertex Buffer"),
+            contents: bytemuck::cast_slice(vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+
+        let index_buffer = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Chunk Index Buffer"),
+            contents: bytemuck::cast_slice(indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        let uniform = ChunkUniform {
+            offset: [
+                (position.0 * CHUNK_SIZE as i32) as f32,
+                0.0,
+                (position.1 * CHUNK_SIZE as i32) as f32,
+                0.0,
+            ],
+        };
+        let offset_buffer = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Chunk Offset Buffer"),
+            contents: bytemuck::cast_slice(&[uniform]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        let offset_bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.chunk_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: offset_buffer.as_entire_binding(),
+            }],
+            label: Some("chunk_bind_group"),
+        });
+
+        self.chunks.insert(
+            position,
+            ChunkBuffers {
+                vertex_buffer,
+                index_buffer,
+                num_indices: indices.len() as u32,
+                offset_bind_group,
+            },
+        );
     }
 
-    fn create_cube_vertices() -> Vec<Vertex> {
-        vec![
-            // Front face (grass green)
-            Vertex { position: [-1.0, -1.0, 1.0], color: [0.2, 0.8, 0.2] },
-            Vertex { position: [1.0, -1.0, 1.0], color: [0.2, 0.8, 0.2] },
-            Vertex { position: [1.0, 1.0, 1.0], color: [0.2, 0.8, 0.2] },
-            Vertex { position: [-1.0, 1.0, 1.0], color: [0.2, 0.8, 0.2] },
-            // Back face
-            Vertex { position: [-1.0, -1.0, -1.0], color: [0.2, 0.7, 0.2] },
-            Vertex { position: [-1.0, 1.0, -1.0], color: [0.2, 0.7, 0.2] },
-            Vertex { position: [1.0, 1.0, -1.0], color: [0.2, 0.7, 0.2] },
-            Vertex { position: [1.0, -1.0, -1.0], color: [0.2, 0.7, 0.2] },
-            // Top face (bright green)
-            Vertex { position: [-1.0, 1.0, -1.0], color: [0.3, 0.9, 0.3] },
-            Vertex { position: [-1.0, 1.0, 1.0], color: [0.3, 0.9, 0.3] },
-            Vertex { position: [1.0, 1.0, 1.0], color: [0.3, 0.9, 0.3] },
-            Vertex { position: [1.0, 1.0, -1.0], color: [0.3, 0.9, 0.3] },
-            // Bottom face (darker)
-            Vertex { position: [-1.0, -1.0, -1.0], color: [0.1, 0.6, 0.1] },
-            Vertex { position: [1.0, -1.0, -1.0], color: [0.1, 0.6, 0.1] },
-            Vertex { position: [1.0, -1.0, 1.0], color: [0.1, 0.6, 0.1] },
-            Vertex { position: [-1.0, -1.0, 1.0], color: [0.1, 0.6, 0.1] },
-            // Right face
-            Vertex { position: [1.0, -1.0, -1.0], color: [0.2, 0.75, 0.2] },
-            Vertex { position: [1.0, 1.0, -1.0], color: [0.2, 0.75, 0.2] },
-            Vertex { position: [1.0, 1.0, 1.0], color: [0.2, 0.75, 0.2] },
-            Vertex { position: [1.0, -1.0, 1.0], color: [0.2, 0.75, 0.2] },
-            // Left face
-            Vertex { position: [-1.0, -1.0, -1.0], color: [0.2, 0.75, 0.2] },
-            Vertex { position: [-1.0, -1.0, 1.0], color: [0.2, 0.75, 0.2] },
-            Vertex { position: [-1.0, 1.0, 1.0], color: [0.2, 0.75, 0.2] },
-            Vertex { position: [-1.0, 1.0, -1.0], color: [0.2, 0.75, 0.2] },
-        ]
+    /// Releases the GPU buffers of the chunk at `position`
+    pub fn remove_chunk(&mut self, position: (i32, i32)) {
+        self.chunks.remove(&position);
     }
 
-    fn create_cube_indices() -> Vec<u16> {
-        vec![
-            0, 1, 2, 2, 3, 0,       // front
-            4, 5, 6, 6, 7, 4,       // back
-            8, 9, 10, 10, 11, 8,    // top
-            12, 13, 14, 14, 15, 12, // bottom
-            16, 17, 18, 18, 19, 16, // right
-            20, 21, 22, 22, 23, 20, // left
-        ]
+    /// Number of chunks with uploaded meshes
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.len()
     }
 
     pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
         if new_size.width > 0 && new_size.height > 0 {
             self.size = new_size;
-            self.config.width = new_size.width;
-            self.config.height = new_size.height;
-            self.surface.configure(&self.device, &self.config);
+            match &mut self.target {
+                RenderTarget::Surface { surface, config } => {
+                    config.width = new_size.width;
+                    config.height = new_size.height;
+                    surface.configure(&self.device, config);
+                }
+                RenderTarget::Offscreen { texture, view } => {
+                    texture.destroy();
+                    (*texture, *view) = Self::create_offscreen_texture(&self.device, new_size);
+                }
+            }
+
+            self.depth_texture.destroy();
+            let (depth_texture, depth_view) = Self::create_depth_texture(&self.device, new_size);
+            self.depth_texture = depth_texture;
+            self.depth_view = depth_view;
         }
     }
 
@@ -304,10 +524,21 @@ impl Renderer {
     }
 
     pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
-        let output = self.surface.get_current_texture()?;
-        let view = output
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
+        let (output, surface_view) = match &self.target {
+            RenderTarget::Surface { surface, .. } => {
+                let output = surface.get_current_texture()?;
+                let view = output
+                    .texture
+                    .create_view(&wgpu::TextureViewDescriptor::default());
+                (Some(output), Some(view))
+            }
+            RenderTarget::Offscreen { .. } => (None, None),
+        };
+        let view = match (&surface_view, &self.target) {
+            (Some(view), _) => view,
+            (None, RenderTarget::Offscreen { view, .. }) => view,
+            (None, RenderTarget::Surface { .. }) => unreachable!("surface frames always have a view"),
+        };
 
         let mut encoder = self
             .device
@@ -319,7 +550,7 @@ impl Renderer {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Render Pass"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
+                    view,
                     resolve_target: None,
                     ops: wgpu::Operations {
                         load: wgpu::LoadOp::Clear(wgpu::Color {
@@ -331,21 +562,111 @@ impl Renderer {
                         store: wgpu::StoreOp::Store,
                     },
                 })],
-                depth_stencil_attachment: None,
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
                 occlusion_query_set: None,
                 timestamp_writes: None,
             });
 
             render_pass.set_pipeline(&self.render_pipeline);
             render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
-            render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-            render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-            render_pass.draw_indexed(0..self.num_indices, 0, 0..1);
+            for chunk in self.chunks.values() {
+                render_pass.set_bind_group(1, &chunk.offset_bind_group, &[]);
+                render_pass.set_vertex_buffer(0, chunk.vertex_buffer.slice(..));
+                render_pass.set_index_buffer(chunk.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                render_pass.draw_indexed(0..chunk.num_indices, 0, 0..1);
+            }
+        }
+
+        self.queue.submit(std::iter::once(encoder.finish()));
+        if let Some(output) = output {
+            output.present();
         }
 
+        Ok(())
+    }
+
+    /// Copies the last rendered frame of a headless renderer back to the CPU
+    ///
+    /// Returns tightly packed RGBA8 rows, top row first.
+    pub fn read_pixels(&self) -> Result<Vec<u8>, RendererError> {
+        let RenderTarget::Offscreen { texture, .. } = &self.target else {
+            return Err(RendererError::NotHeadless);
+        };
+
+        let (width, height) = (self.size.width, self.size.height);
+        let unpadded_row = width * 4;
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_row = unpadded_row.div_ceil(align) * align;
+
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Readback Buffer"),
+            size: (padded_row * height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Readback Encoder"),
+            });
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
         self.queue.submit(std::iter::once(encoder.finish()));
-        output.present();
 
+        let slice = buffer.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+        receiver
+            .recv()
+            .expect("map_async callback dropped")
+            .map_err(RendererError::BufferMap)?;
+
+        let data = slice.get_mapped_range();
+        let mut pixels = Vec::with_capacity((unpadded_row * height) as usize);
+        for row in data.chunks(padded_row as usize) {
+            pixels.extend_from_slice(&row[..unpadded_row as usize]);
+        }
+        drop(data);
+        buffer.unmap();
+
+        Ok(pixels)
+    }
+
+    /// Saves the last rendered frame of a headless renderer as a PNG file
+    pub fn save_png(&self, path: impl AsRef<std::path::Path>) -> Result<(), RendererError> {
+        let pixels = self.read_pixels()?;
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        let mut encoder = png::Encoder::new(file, self.size.width, self.size.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&pixels)?;
+        writer.finish()?;
         Ok(())
     }
 
@@ -353,3 +674,132 @@ impl Renderer {
         self.size
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::BlockType;
+    use crate::chunk::Chunk;
+    use crate::mesh::{mesh_chunk, ChunkNeighbors, MeshingMode};
+    use std::path::PathBuf;
+
+    const WIDTH: u32 = 128;
+    const HEIGHT: u32 = 96;
+
+    /// Creates a headless renderer
+    ///
+    /// Tests using it are ignored by default and run with
+    /// `cargo test -- --ignored`, so a machine without an adapter reports
+    /// them as ignored instead of passing them.
+    fn headless() -> Renderer {
+        pollster::block_on(Renderer::new_headless(WIDTH, HEIGHT))
+            .unwrap_or_else(|e| panic!("failed to create headless renderer: {}", e))
+    }
+
+    fn camera() -> Camera {
+        let mut camera = Camera::new(WIDTH as f32 / HEIGHT as f32);
+        camera.position = glam::Vec3::new(-6.0, 8.0, 8.0);
+        camera.yaw = 0.0;
+        camera.pitch = -20.0_f32.to_radians();
+        camera
+    }
+
+    fn pixel(pixels: &[u8], x: u32, y: u32) -> [u8; 4] {
+        let i = ((y * WIDTH + x) * 4) as usize;
+        [pixels[i], pixels[i + 1], pixels[i + 2], pixels[i + 3]]
+    }
+
+    /// Compares against a golden PNG, allowing small rasterizer differences.
+    /// Set `UPDATE_GOLDEN=1` to rewrite the golden image.
+    fn assert_matches_golden(renderer: &Renderer, name: &str) {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/golden")
+            .join(format!("{}.png", name));
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            renderer.save_png(&path).unwrap();
+        }
+
+        let decoder = png::Decoder::new(std::fs::File::open(&path).unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        let mut golden = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut golden).unwrap();
+
+        let pixels = renderer.read_pixels().unwrap();
+        assert_eq!(pixels.len(), golden.len());
+        let mismatched = pixels
+            .chunks(4)
+            .zip(golden.chunks(4))
+            .filter(|(a, b)| a.iter().zip(b.iter()).any(|(x, y)| x.abs_diff(*y) > 8))
+            .count();
+        // Edges may rasterize slightly differently between adapters
+        assert!(
+            mismatched * 100 <= (WIDTH * HEIGHT) as usize,
+            "{} pixels differ from {}",
+            mismatched,
+            path.display()
+        );
+    }
+
+    #[test]
+    #[ignore = "needs a graphics adapter"]
+    fn test_empty_scene_is_sky() {
+        let mut renderer = headless();
+        renderer.update_camera(&camera());
+        renderer.render().unwrap();
+        let pixels = renderer.read_pixels().unwrap();
+        assert_eq!(pixels.len(), (WIDTH * HEIGHT * 4) as usize);
+        let sky = pixel(&pixels, 0, 0);
+        assert!(pixels.chunks(4).all(|p| p == sky));
+    }
+
+    #[test]
+    #[ignore = "needs a graphics adapter"]
+    fn test_depth_occludes_far_blocks() {
+        let mut renderer = headless();
+
+        // A stone wall in front of a grass wall, with the far wall meshed first
+        let mut chunk = Chunk::new((0, 0));
+        for y in 0..16 {
+            for z in 0..16 {
+                chunk.set_block(12, y, z, BlockType::Grass);
+                chunk.set_block(4, y, z, BlockType::Stone);
+            }
+        }
+        let mesh = mesh_chunk(&chunk, &ChunkNeighbors::default(), MeshingMode::Greedy);
+        renderer.upload_chunk_mesh((0, 0), &mesh.vertices, &mesh.indices);
+
+        let mut camera = camera();
+        camera.position = glam::Vec3::new(-4.0, 8.0, 8.0);
+        camera.pitch = 0.0;
+        renderer.update_camera(&camera);
+        renderer.render().unwrap();
+
+        let center = pixel(&renderer.read_pixels().unwrap(), WIDTH / 2, HEIGHT / 2);
+        // Stone is grey, grass is green
+        assert!(center[0].abs_diff(center[1]) < 8, "expected stone, got {:?}", center);
+    }
+
+    #[test]
+    #[ignore = "needs a graphics adapter"]
+    fn test_terrain_matches_golden_image() {
+        let mut renderer = headless();
+
+        let mut chunk = Chunk::new((0, 0));
+        for x in 0..8 {
+            for z in 0..8 {
+                for y in 0..=(x + z) / 3 {
+                    let block = if y == (x + z) / 3 { BlockType::Grass } else { BlockType::Dirt };
+                    chunk.set_block(x, y, z, block);
+                }
+            }
+        }
+        chunk.set_block(6, 6, 2, BlockType::Stone);
+        let mesh = mesh_chunk(&chunk, &ChunkNeighbors::default(), MeshingMode::Greedy);
+        renderer.upload_chunk_mesh((0, 0), &mesh.vertices, &mesh.indices);
+        renderer.update_camera(&camera());
+        renderer.render().unwrap();
+
+        assert_matches_golden(&renderer, "staircase");
+    }
+}
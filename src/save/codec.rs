@@ -0,0 +1,216 @@
+use std::io::{Read, Write};
+
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+
+use crate::block::BlockType;
+use crate::chunk::{Chunk, MAX_Y, MIN_Y, SECTION_COUNT, SECTION_SIZE};
+
+use super::migrate::migrate_chunk;
+use super::{BlockIds, SaveError, FORMAT_VERSION};
+
+const SECTION_VOLUME: usize = SECTION_SIZE * SECTION_SIZE * SECTION_SIZE;
+
+/// Serializes a chunk into a compressed payload for a region file
+///
+/// The payload holds the format version, the chunk position and the number
+/// of sections that hold blocks. Each of those sections follows as its
+/// height in sections (y / 16), its palette of block IDs from `ids` and,
+/// unless the section is uniform, one palette index per block: a byte each
+/// for palettes of up to 256 entries and two bytes each beyond that.
+pub fn encode_chunk(chunk: &Chunk, ids: &BlockIds) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&chunk.position.0.to_le_bytes());
+    bytes.extend_from_slice(&chunk.position.1.to_le_bytes());
+    let sections: Vec<_> = (0..SECTION_COUNT)
+        .filter_map(|index| Some((index, chunk.section(index)?)))
+        .collect();
+    bytes.push(sections.len() as u8);
+
+    let mut entries = vec![0u16; BlockType::ALL.len()];
+    for (index, section) in sections {
+        let section_y = Chunk::section_min_y(index).div_euclid(SECTION_SIZE as i32);
+        bytes.push(section_y as i8 as u8);
+        let palette: Vec<BlockType> = section.blocks().palette().collect();
+        bytes.extend_from_slice(&(palette.len() as u16).to_le_bytes());
+        for (entry, &block) in palette.iter().enumerate() {
+            bytes.extend_from_slice(&ids.id(block).to_le_bytes());
+            entries[block.id() as usize] = entry as u16;
+        }
+        if palette.len() == 1 {
+            continue;
+        }
+        for block in section.blocks().iter() {
+            let entry = entries[block.id() as usize];
+            if palette.len() <= 256 {
+                bytes.push(entry as u8);
+            } else {
+                bytes.extend_from_slice(&entry.to_le_bytes());
+            }
+        }
+    }
+
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&bytes).expect("compressing into memory cannot fail");
+    encoder.finish().expect("compressing into memory cannot fail")
+}
+
+/// Rebuilds a chunk from a payload written by [`encode_chunk`] in this or
+/// any earlier format version
+///
+/// The chunk comes back unmodified, as it matches what is on disk.
+pub fn decode_chunk(payload: &[u8], ids: &BlockIds) -> Result<Chunk, SaveError> {
+    let mut bytes = Vec::new();
+    ZlibDecoder::new(payload)
+        .read_to_end(&mut bytes)
+        .map_err(|e| SaveError::Corrupt(format!("chunk data does not decompress: {}", e)))?;
+    let bytes = migrate_chunk(bytes)?;
+    let mut reader = Reader::new(&bytes);
+
+    reader.u32()?;
+    let position = (reader.u32()? as i32, reader.u32()? as i32);
+    let mut chunk = Chunk::new(position);
+    for _ in 0..reader.u8()? {
+        let min_y = reader.u8()? as i8 as i32 * SECTION_SIZE as i32;
+        if !(MIN_Y..MAX_Y).contains(&min_y) {
+            return Err(SaveError::Corrupt(format!("section at y = {} is out of range", min_y)));
+        }
+        let palette = (0..reader.u16()?)
+            .map(|_| ids.block(reader.u16()?))
+            .collect::<Result<Vec<_>, _>>()?;
+        for slot in 0..SECTION_VOLUME {
+            let block = match palette.len() {
+                0 => return Err(SaveError::Corrupt("empty section palette".to_string())),
+                1 => palette[0],
+                len => {
+                    let entry = if len <= 256 {
+                        reader.u8()? as usize
+                    } else {
+                        reader.u16()? as usize
+                    };
+                    *palette.get(entry).ok_or_else(|| {
+                        SaveError::Corrupt(format!("palette index {} out of {}", entry, len))
+                    })?
+                }
+            };
+            if block != BlockType::Air {
+                let (x, z) = (slot % SECTION_SIZE, slot / SECTION_SIZE % SECTION_SIZE);
+                let y = min_y + (slot / (SECTION_SIZE * SECTION_SIZE)) as i32;
+                chunk.set_block_at(x, y, z, block);
+            }
+        }
+    }
+    if !reader.is_at_end() {
+        return Err(SaveError::Corrupt("trailing bytes after chunk data".to_string()));
+    }
+    chunk.mark_saved(chunk.revision());
+    Ok(chunk)
+}
+
+/// Reads little-endian values from a decompressed payload
+pub(super) struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub(super) fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+
+    /// Bytes read so far
+    pub(super) fn position(&self) -> usize {
+        self.position
+    }
+
+    pub(super) fn is_at_end(&self) -> bool {
+        self.position == self.bytes.len()
+    }
+
+    pub(super) fn skip(&mut self, len: usize) -> Result<(), SaveError> {
+        if self.bytes.len() - self.position < len {
+            return Err(SaveError::Corrupt("chunk data ends early".to_string()));
+        }
+        self.position += len;
+        Ok(())
+    }
+
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], SaveError> {
+        let start = self.position;
+        self.skip(N)?;
+        Ok(self.bytes[start..self.position].try_into().unwrap())
+    }
+
+    pub(super) fn u8(&mut self) -> Result<u8, SaveError> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    pub(super) fn u16(&mut self) -> Result<u16, SaveError> {
+        self.take().map(u16::from_le_bytes)
+    }
+
+    pub(super) fn u32(&mut self) -> Result<u32, SaveError> {
+        self.take().map(u32::from_le_bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::CHUNK_SIZE;
+
+    #[test]
+    fn test_chunk_payload_round_trip() {
+        let mut chunk = Chunk::new((-7, 12));
+        // A uniform section, a varied one, and blocks at both height limits
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                for y in 0..SECTION_SIZE as i32 {
+                    chunk.set_block_at(x, y, z, BlockType::Stone);
+                }
+                let block = BlockType::ALL[(x * 3 + z) % BlockType::ALL.len()];
+                chunk.set_block_at(x, 20, z, block);
+            }
+        }
+        chunk.set_block_at(0, MIN_Y, 0, BlockType::Bedrock);
+        chunk.set_block_at(15, MAX_Y - 1, 15, BlockType::Snow);
+
+        let ids = BlockIds::default();
+        let payload = encode_chunk(&chunk, &ids);
+        assert!(decode_chunk(&payload, &ids).unwrap() == chunk);
+        let empty = Chunk::new((0, 0));
+        assert!(decode_chunk(&encode_chunk(&empty, &ids), &ids).unwrap() == empty);
+        // A whole section of stone packs into a few bytes
+        assert!(payload.len() < 600, "{} bytes", payload.len());
+    }
+
+    #[test]
+    fn test_truncated_payload_is_rejected() {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&FORMAT_VERSION.to_le_bytes()).unwrap();
+        let truncated = encoder.finish().unwrap();
+        let ids = BlockIds::default();
+        assert!(matches!(decode_chunk(&truncated, &ids), Err(SaveError::Corrupt(_))));
+        assert!(matches!(decode_chunk(b"garbage", &ids), Err(SaveError::Corrupt(_))));
+    }
+
+    #[test]
+    fn test_payload_uses_world_block_ids() {
+        let mut chunk = Chunk::new((0, 0));
+        chunk.set_block(0, 0, 0, BlockType::Stone);
+        chunk.set_block(1, 0, 0, BlockType::Sand);
+
+        // Swap the IDs of stone and sand, as an older build might have had
+        let mut names = BlockIds::default().names().clone();
+        names.insert("stone".to_string(), BlockType::Sand.id());
+        names.insert("sand".to_string(), BlockType::Stone.id());
+        let world = BlockIds::from_names(&names).unwrap();
+
+        let payload = encode_chunk(&chunk, &world);
+        assert!(decode_chunk(&payload, &world).unwrap() == chunk);
+        let misread = decode_chunk(&payload, &BlockIds::default()).unwrap();
+        assert_eq!(misread.get_block(0, 0, 0), Some(BlockType::Sand));
+    }
+}
@@ -0,0 +1,119 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::block::BlockType;
+
+use super::SaveError;
+
+/// The numeric IDs a world's chunks store blocks under
+///
+/// The table is kept by block name in the world's metadata, so reordering or
+/// adding `BlockType` variants never changes what saved chunks mean. Blocks
+/// added to the game after the world was created get the next free IDs, and
+/// names this build does not know keep their IDs reserved.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockIds {
+    names: BTreeMap<String, u16>,
+    blocks: HashMap<u16, BlockType>,
+    ids: HashMap<BlockType, u16>,
+}
+
+impl Default for BlockIds {
+    /// IDs for a new world, in `BlockType` declaration order
+    fn default() -> Self {
+        let names = BlockType::ALL
+            .iter()
+            .map(|block| (block.name().to_string(), block.id()))
+            .collect();
+        Self::from_names(&names).expect("default block IDs are unique")
+    }
+}
+
+impl BlockIds {
+    /// Builds the table from a world's name to ID mapping, adding any block
+    /// the mapping does not have yet
+    pub fn from_names(names: &BTreeMap<String, u16>) -> Result<Self, SaveError> {
+        let mut table = Self {
+            names: names.clone(),
+            blocks: HashMap::new(),
+            ids: HashMap::new(),
+        };
+        let mut used = HashMap::new();
+        for (name, &id) in names {
+            if let Some(other) = used.insert(id, name) {
+                return Err(SaveError::Corrupt(format!(
+                    "blocks {} and {} share ID {}",
+                    other, name, id
+                )));
+            }
+            if let Some(block) = BlockType::from_name(name) {
+                table.blocks.insert(id, block);
+                table.ids.insert(block, id);
+            }
+        }
+        let mut next = names.values().max().map_or(0, |&id| id + 1);
+        for &block in BlockType::ALL {
+            if !table.ids.contains_key(&block) {
+                table.names.insert(block.name().to_string(), next);
+                table.blocks.insert(next, block);
+                table.ids.insert(block, next);
+                next += 1;
+            }
+        }
+        Ok(table)
+    }
+
+    /// The name to ID mapping, as stored in the world's metadata
+    pub fn names(&self) -> &BTreeMap<String, u16> {
+        &self.names
+    }
+
+    pub fn id(&self, block: BlockType) -> u16 {
+        self.ids[&block]
+    }
+
+    /// The block saved under `id`
+    pub fn block(&self, id: u16) -> Result<BlockType, SaveError> {
+        if let Some(&block) = self.blocks.get(&id) {
+            return Ok(block);
+        }
+        match self.names.iter().find(|&(_, &other)| other == id) {
+            Some((name, _)) => Err(SaveError::Corrupt(format!("unknown block {}", name))),
+            None => Err(SaveError::Corrupt(format!("unknown block ID {}", id))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_world_ids_survive_reordering() {
+        // A world made by a build that numbered blocks differently
+        let names: BTreeMap<String, u16> =
+            [("stone", 0), ("air", 1), ("dirt", 5), ("mossy_bricks", 2)]
+                .into_iter()
+                .map(|(name, id)| (name.to_string(), id))
+                .collect();
+        let ids = BlockIds::from_names(&names).unwrap();
+        assert_eq!(ids.block(0).unwrap(), BlockType::Stone);
+        assert_eq!(ids.id(BlockType::Air), 1);
+        assert_eq!(ids.id(BlockType::Dirt), 5);
+
+        // Blocks the world has not seen yet get fresh IDs after the last one
+        assert!(ids.id(BlockType::Grass) > 5);
+        assert_eq!(ids.names().len(), BlockType::ALL.len() + 1);
+        assert_eq!(BlockIds::from_names(ids.names()).unwrap(), ids);
+
+        // Names from another build stay reserved but cannot be loaded
+        assert!(matches!(ids.block(2), Err(SaveError::Corrupt(_))));
+        assert!(matches!(ids.block(999), Err(SaveError::Corrupt(_))));
+    }
+
+    #[test]
+    fn test_duplicate_ids_are_rejected() {
+        let names: BTreeMap<String, u16> =
+            [("stone".to_string(), 3), ("dirt".to_string(), 3)].into_iter().collect();
+        assert!(matches!(BlockIds::from_names(&names), Err(SaveError::Corrupt(_))));
+    }
+}
@@ -0,0 +1,471 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::chunk::Chunk;
+use crate::world::World;
+use crate::worldgen::WorldGenerator;
+
+use super::{SaveError, WorldSave};
+
+/// Copies of chunks handed to the writer thread that are not on disk yet
+type Unwritten = Arc<Mutex<HashMap<(i32, i32), Chunk>>>;
+
+/// Chunks whose saved data failed to load
+#[derive(Default)]
+struct LoadFailures {
+    /// Positions kept out of every save, so the data on disk is left alone
+    positions: HashSet<(i32, i32)>,
+    /// Errors not yet returned by `update` or `flush`
+    unreported: Vec<SaveError>,
+}
+
+/// Outcome of one batch written by the writer thread
+enum WriteResult {
+    /// Chunks now on disk, with the revision that was written
+    Saved(Vec<((i32, i32), u64)>),
+    Failed(SaveError),
+}
+
+/// Writes modified chunks to a save in the background
+///
+/// Call [`SaveManager::update`] every frame. Once per interval it copies the
+/// chunks whose revision changed since they were last saved and hands them
+/// to a writer thread, which encodes them and replaces the region files
+/// atomically. Unchanged chunks are never written, and freshly generated
+/// ones only once they are edited.
+///
+/// Chunks stay queued until they are on disk, so a failed write is retried
+/// at the next autosave. Chunks that fail to load are never written, so a
+/// damaged chunk is not replaced by whatever stands in for it. Dropping the
+/// manager finishes the queued writes.
+pub struct SaveManager {
+    save: Arc<WorldSave>,
+    interval: Duration,
+    last_autosave: Instant,
+    unwritten: Unwritten,
+    failures: Arc<Mutex<LoadFailures>>,
+    sender: Option<Sender<Vec<(i32, i32)>>>,
+    results: Receiver<WriteResult>,
+    /// Batches sent to the writer whose result has not come back
+    in_flight: usize,
+    writer: Option<JoinHandle<()>>,
+}
+
+impl SaveManager {
+    /// Starts a writer thread saving into `save` every `interval`
+    pub fn new(save: WorldSave, interval: Duration) -> Self {
+        let save = Arc::new(save);
+        let unwritten = Unwritten::default();
+        let (sender, batches) = mpsc::channel::<Vec<(i32, i32)>>();
+        let (result_sender, results) = mpsc::channel();
+        let writer = {
+            let save = Arc::clone(&save);
+            let unwritten = Arc::clone(&unwritten);
+            std::thread::Builder::new()
+                .name("save-writer".to_string())
+                .spawn(move || {
+                    for batch in batches {
+                        let result = write_batch(&save, &unwritten, batch);
+                        if result_sender.send(result).is_err() {
+                            break;
+                        }
+                    }
+                })
+                .expect("failed to spawn save writer thread")
+        };
+        Self {
+            save,
+            interval,
+            last_autosave: Instant::now(),
+            unwritten,
+            failures: Arc::default(),
+            sender: Some(sender),
+            results,
+            in_flight: 0,
+            writer: Some(writer),
+        }
+    }
+
+    pub fn save(&self) -> &WorldSave {
+        &self.save
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Number of chunks waiting to be written
+    pub fn unwritten(&self) -> usize {
+        self.unwritten.lock().unwrap().len()
+    }
+
+    /// Applies finished writes and autosaves once the interval has passed
+    ///
+    /// Returns the first error reported by the writer or by loading a chunk
+    /// since the last call.
+    pub fn update(&mut self, world: &mut World) -> Result<(), SaveError> {
+        let result = self.collect_results(world, false).and(self.load_errors());
+        if self.last_autosave.elapsed() >= self.interval {
+            self.autosave(world);
+        }
+        result
+    }
+
+    /// Queues every modified chunk in `world` for writing, along with any
+    /// chunk whose earlier write failed, and returns how many were copied
+    pub fn autosave(&mut self, world: &World) -> usize {
+        self.last_autosave = Instant::now();
+        let mut copied = 0;
+        let batch: Vec<(i32, i32)> = {
+            let failures = self.failures.lock().unwrap();
+            let mut unwritten = self.unwritten.lock().unwrap();
+            let modified = world.chunks().filter(|chunk| chunk.is_modified());
+            for chunk in modified.filter(|chunk| !failures.positions.contains(&chunk.position)) {
+                let queued = unwritten.get(&chunk.position).map(Chunk::revision);
+                if queued != Some(chunk.revision()) {
+                    unwritten.insert(chunk.position, chunk.clone());
+                    copied += 1;
+                }
+            }
+            unwritten.keys().copied().collect()
+        };
+        self.send(batch);
+        copied
+    }
+
+    /// Queues chunks that were removed from the world, such as those the
+    /// streamer evicted, so their changes are not lost
+    pub fn save_chunks(&mut self, chunks: impl IntoIterator<Item = Chunk>) {
+        let batch: Vec<(i32, i32)> = {
+            let failures = self.failures.lock().unwrap();
+            let mut unwritten = self.unwritten.lock().unwrap();
+            chunks
+                .into_iter()
+                .filter(|chunk| chunk.is_modified())
+                .filter(|chunk| !failures.positions.contains(&chunk.position))
+                .map(|chunk| {
+                    let position = chunk.position;
+                    unwritten.insert(position, chunk);
+                    position
+                })
+                .collect()
+        };
+        self.send(batch);
+    }
+
+    /// Queues every modified chunk and blocks until all queued chunks are
+    /// written, as when the game exits
+    pub fn flush(&mut self, world: &mut World) -> Result<(), SaveError> {
+        self.autosave(world);
+        self.collect_results(world, true).and(self.load_errors())
+    }
+
+    /// Wraps `generator` so that chunks are loaded from the save, or from
+    /// the chunks still waiting to be written, before being generated
+    pub fn generator(&self, generator: Arc<dyn WorldGenerator>) -> Arc<dyn WorldGenerator> {
+        Arc::new(SavedWorldGenerator {
+            save: Arc::clone(&self.save),
+            unwritten: Arc::clone(&self.unwritten),
+            failures: Arc::clone(&self.failures),
+            generator,
+        })
+    }
+
+    fn send(&mut self, batch: Vec<(i32, i32)>) {
+        if batch.is_empty() {
+            return;
+        }
+        if let Some(sender) = &self.sender {
+            if sender.send(batch).is_ok() {
+                self.in_flight += 1;
+            }
+        }
+    }
+
+    /// Returns the first load error since the last call, dropping the rest
+    fn load_errors(&self) -> Result<(), SaveError> {
+        let mut failures = self.failures.lock().unwrap();
+        let first = failures.unreported.drain(..).next();
+        first.map_or(Ok(()), Err)
+    }
+
+    /// Marks written chunks as saved in `world`, optionally waiting for
+    /// every batch in flight
+    fn collect_results(&mut self, world: &mut World, wait: bool) -> Result<(), SaveError> {
+        let mut first_error = None;
+        while self.in_flight > 0 {
+            let result = if wait {
+                match self.results.recv() {
+                    Ok(result) => result,
+                    Err(_) => break,
+                }
+            } else {
+                match self.results.try_recv() {
+                    Ok(result) => result,
+                    Err(_) => break,
+                }
+            };
+            self.in_flight -= 1;
+            match result {
+                WriteResult::Saved(chunks) => {
+                    for (position, revision) in chunks {
+                        if let Some(chunk) = world.get_chunk_mut(position) {
+                            chunk.mark_saved(revision);
+                        }
+                    }
+                }
+                WriteResult::Failed(e) => {
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        first_error.map_or(Ok(()), Err)
+    }
+}
+
+impl Drop for SaveManager {
+    fn drop(&mut self) {
+        self.sender.take();
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+/// Writes the queued copies of `positions`, keeping any that changed again
+/// while they were being written
+fn write_batch(save: &WorldSave, unwritten: &Unwritten, positions: Vec<(i32, i32)>) -> WriteResult {
+    let chunks: Vec<Chunk> = {
+        let unwritten = unwritten.lock().unwrap();
+        positions.iter().filter_map(|position| unwritten.get(position).cloned()).collect()
+    };
+    if chunks.is_empty() {
+        return WriteResult::Saved(Vec::new());
+    }
+    if let Err(e) = save.save_chunks(&chunks) {
+        return WriteResult::Failed(e);
+    }
+    let mut unwritten = unwritten.lock().unwrap();
+    let saved = chunks
+        .iter()
+        .map(|chunk| {
+            let position = chunk.position;
+            if unwritten.get(&position).map(Chunk::revision) == Some(chunk.revision()) {
+                unwritten.remove(&position);
+            }
+            (position, chunk.revision())
+        })
+        .collect();
+    WriteResult::Saved(saved)
+}
+
+/// Loads chunks from a save, falling back to a generator for chunks that
+/// were never saved
+///
+/// A chunk that fails to load is left empty rather than generated, and its
+/// error is handed to the manager.
+struct SavedWorldGenerator {
+    save: Arc<WorldSave>,
+    unwritten: Unwritten,
+    failures: Arc<Mutex<LoadFailures>>,
+    generator: Arc<dyn WorldGenerator>,
+}
+
+impl WorldGenerator for SavedWorldGenerator {
+    fn generate_chunk(&self, chunk: &mut Chunk) {
+        let position = chunk.position;
+        if let Some(queued) = self.unwritten.lock().unwrap().get(&position) {
+            *chunk = queued.clone();
+            return;
+        }
+        match self.save.load_chunk(position) {
+            Ok(Some(saved)) => *chunk = saved,
+            Ok(None) => self.generator.generate_chunk(chunk),
+            Err(e) => {
+                *chunk = Chunk::new(position);
+                let mut failures = self.failures.lock().unwrap();
+                failures.positions.insert(position);
+                failures.unreported.push(SaveError::ChunkLoad(position, Box::new(e)));
+            }
+        }
+    }
+
+    fn seed(&self) -> u32 {
+        self.generator.seed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::BlockType;
+    use crate::save::{RegionFile, WorldMetadata, REGION_DIR};
+    use crate::worldgen::{GeneratorSettings, TerrainGenerator};
+
+    fn manager(name: &str, interval: Duration) -> SaveManager {
+        let dir = std::env::temp_dir()
+            .join(format!("manager-test-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let metadata = WorldMetadata::new(GeneratorSettings::default(), [0, 40, 0]);
+        SaveManager::new(WorldSave::create(&dir, &metadata).unwrap(), interval)
+    }
+
+    /// A world of generated chunks, as the streamer would load them
+    fn generated_world(generator: &dyn WorldGenerator) -> World {
+        let mut world = World::new();
+        for x in -1..=1 {
+            let mut chunk = Chunk::new((x, 0));
+            generator.generate_chunk(&mut chunk);
+            chunk.mark_saved(chunk.revision());
+            world.insert_chunk(chunk);
+        }
+        world
+    }
+
+    fn region_files(manager: &SaveManager) -> Vec<String> {
+        let mut names: Vec<String> = std::fs::read_dir(manager.save().dir().join(REGION_DIR))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_only_modified_chunks_are_written() {
+        let mut manager = manager("modified", Duration::from_secs(3600));
+        let generator = TerrainGenerator::default();
+        let mut world = generated_world(&generator);
+
+        // Nothing has changed, so nothing is written
+        manager.flush(&mut world).unwrap();
+        assert!(region_files(&manager).is_empty());
+
+        world.set_block(20, 100, 3, BlockType::Planks);
+        assert_eq!(manager.autosave(&world), 1);
+        // Queuing the same revision again copies nothing
+        assert_eq!(manager.autosave(&world), 0);
+        manager.flush(&mut world).unwrap();
+        assert_eq!(manager.unwritten(), 0);
+        assert!(world.chunks().all(|chunk| !chunk.is_modified()));
+        assert_eq!(region_files(&manager), vec!["r.0.0.vxr"]);
+
+        let saved = manager.save().load_chunk((1, 0)).unwrap().unwrap();
+        assert!(&saved == world.get_chunk((1, 0)).unwrap());
+        assert!(manager.save().load_chunk((0, 0)).unwrap().is_none());
+        std::fs::remove_dir_all(manager.save().dir()).unwrap();
+    }
+
+    #[test]
+    fn test_autosave_runs_on_interval() {
+        let mut manager = manager("interval", Duration::ZERO);
+        let mut world = World::new();
+        world.set_block(-5, 10, -5, BlockType::Stone);
+        manager.update(&mut world).unwrap();
+
+        // The next update after the write lands marks the chunk saved
+        for _ in 0..1000 {
+            manager.update(&mut world).unwrap();
+            if !world.get_chunk((-1, -1)).unwrap().is_modified() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        assert!(!world.get_chunk((-1, -1)).unwrap().is_modified());
+        assert_eq!(region_files(&manager), vec!["r.-1.-1.vxr"]);
+        std::fs::remove_dir_all(manager.save().dir()).unwrap();
+    }
+
+    #[test]
+    fn test_evicted_chunks_load_back_through_generator() {
+        let mut manager = manager("evicted", Duration::from_secs(3600));
+        let terrain: Arc<dyn WorldGenerator> = Arc::new(TerrainGenerator::default());
+        let generator = manager.generator(Arc::clone(&terrain));
+        let mut world = generated_world(terrain.as_ref());
+        world.set_block(0, 150, 0, BlockType::Cobblestone);
+        let edited = world.remove_chunk((0, 0)).unwrap();
+
+        manager.save_chunks([edited.clone()]);
+        // Whether or not the write has landed, the edit comes back
+        let mut loaded = Chunk::new((0, 0));
+        generator.generate_chunk(&mut loaded);
+        assert!(loaded == edited);
+
+        manager.flush(&mut world).unwrap();
+        let mut loaded = Chunk::new((0, 0));
+        generator.generate_chunk(&mut loaded);
+        assert!(loaded == edited);
+        assert!(!loaded.is_modified());
+
+        // Chunks that were never saved are generated
+        let mut fresh = Chunk::new((9, 9));
+        generator.generate_chunk(&mut fresh);
+        let mut expected = Chunk::new((9, 9));
+        terrain.generate_chunk(&mut expected);
+        assert!(fresh == expected);
+        std::fs::remove_dir_all(manager.save().dir()).unwrap();
+    }
+
+    #[test]
+    fn test_unloadable_chunks_are_reported_and_never_overwritten() {
+        let mut manager = manager("unloadable", Duration::from_secs(3600));
+        let generator = manager.generator(Arc::new(TerrainGenerator::default()));
+        let mut world = World::new();
+        world.set_block(3, 10, 3, BlockType::Log);
+        manager.flush(&mut world).unwrap();
+        world.remove_chunk((0, 0));
+
+        // Damage the saved payload of the chunk
+        let path = manager.save().dir().join(REGION_DIR).join("r.0.0.vxr");
+        let mut region = RegionFile::read(&path, (0, 0)).unwrap().unwrap();
+        region.set_payload((0, 0), b"not a chunk".to_vec());
+        region.write(&path).unwrap();
+        let damaged = std::fs::read(&path).unwrap();
+
+        // It loads as an empty chunk instead of fresh terrain
+        let mut chunk = Chunk::new((0, 0));
+        generator.generate_chunk(&mut chunk);
+        assert!(chunk == Chunk::new((0, 0)));
+        world.insert_chunk(chunk);
+        world.set_block(5, 10, 5, BlockType::Planks);
+
+        let error = manager.flush(&mut world).unwrap_err();
+        assert!(matches!(error, SaveError::ChunkLoad((0, 0), _)), "{}", error);
+        assert_eq!(manager.unwritten(), 0);
+        assert_eq!(std::fs::read(&path).unwrap(), damaged);
+
+        // The error is reported once, and later saves still skip the chunk
+        world.set_block(6, 10, 5, BlockType::Planks);
+        manager.save_chunks(world.remove_chunk((0, 0)));
+        manager.flush(&mut world).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), damaged);
+        std::fs::remove_dir_all(manager.save().dir()).unwrap();
+    }
+
+    #[test]
+    fn test_interrupted_write_keeps_previous_region() {
+        let mut manager = manager("interrupted", Duration::from_secs(3600));
+        let mut world = World::new();
+        world.set_block(1, 1, 1, BlockType::Log);
+        manager.flush(&mut world).unwrap();
+
+        // A crash mid-write leaves only a partial temporary file behind
+        let region = manager.save().dir().join(REGION_DIR).join("r.0.0.vxr");
+        let temp = manager.save().dir().join(REGION_DIR).join("r.0.0.vxr.tmp");
+        std::fs::write(&temp, b"VXRG partial").unwrap();
+        let loaded = manager.save().load_chunk((0, 0)).unwrap().unwrap();
+        assert_eq!(loaded.get_block(1, 1, 1), Some(BlockType::Log));
+
+        // The next save replaces both
+        world.set_block(2, 1, 1, BlockType::Log);
+        manager.flush(&mut world).unwrap();
+        assert!(!temp.exists());
+        assert!(region.exists());
+        let loaded = manager.save().load_chunk((0, 0)).unwrap().unwrap();
+        assert_eq!(loaded.get_block(2, 1, 1), Some(BlockType::Log));
+        std::fs::remove_dir_all(manager.save().dir()).unwrap();
+    }
+}
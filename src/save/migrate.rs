@@ -0,0 +1,230 @@
+use super::codec::Reader;
+use super::{SaveError, FORMAT_VERSION};
+
+/// Upgrades saved data from one format version to the next
+///
+/// Loading runs every step from the data's version up to
+/// [`FORMAT_VERSION`], so each step only has to know the layout of the
+/// version before it. Changing the format means bumping `FORMAT_VERSION`,
+/// adding a step here and a fixture world of the old version under
+/// `tests/fixtures`.
+struct Migration {
+    /// Version the step upgrades from, to `from + 1`
+    from: u32,
+    /// Rewrites a decompressed chunk payload
+    chunk: fn(&[u8]) -> Result<Vec<u8>, SaveError>,
+    /// Rewrites the contents of `level.toml`
+    metadata: fn(&mut toml::Table) -> Result<(), SaveError>,
+}
+
+const MIGRATIONS: &[Migration] = &[Migration {
+    from: 1,
+    chunk: chunk_v1_to_v2,
+    metadata: metadata_v1_to_v2,
+}];
+
+/// Upgrades a decompressed chunk payload to the current format version
+pub fn migrate_chunk(mut bytes: Vec<u8>) -> Result<Vec<u8>, SaveError> {
+    loop {
+        let version = Reader::new(&bytes).u32()?;
+        if version == FORMAT_VERSION {
+            return Ok(bytes);
+        }
+        bytes = (step(version)?.chunk)(&bytes)?;
+    }
+}
+
+/// Upgrades the contents of `level.toml` to the current format version,
+/// returning true if anything changed
+pub fn migrate_metadata(table: &mut toml::Table) -> Result<bool, SaveError> {
+    let mut migrated = false;
+    loop {
+        let version = table
+            .get("format_version")
+            .and_then(toml::Value::as_integer)
+            .ok_or_else(|| SaveError::Corrupt("metadata has no format_version".to_string()))?;
+        let version = u32::try_from(version)
+            .map_err(|_| SaveError::Corrupt(format!("format version {}", version)))?;
+        if version == FORMAT_VERSION {
+            return Ok(migrated);
+        }
+        (step(version)?.metadata)(table)?;
+        table.insert("format_version".to_string(), toml::Value::Integer(version as i64 + 1));
+        migrated = true;
+    }
+}
+
+fn step(version: u32) -> Result<&'static Migration, SaveError> {
+    MIGRATIONS
+        .iter()
+        .find(|migration| migration.from == version)
+        .ok_or(SaveError::UnsupportedVersion(version))
+}
+
+/// Block names in the order format 1 numbered them
+const V1_BLOCKS: [&str; 18] = [
+    "air",
+    "grass",
+    "dirt",
+    "stone",
+    "water",
+    "sand",
+    "snow",
+    "bedrock",
+    "coal_ore",
+    "iron_ore",
+    "gold_ore",
+    "diamond_ore",
+    "log",
+    "leaves",
+    "tall_grass",
+    "flower",
+    "planks",
+    "cobblestone",
+];
+
+/// Format 1 numbered blocks by `BlockType` declaration order; format 2
+/// stores the numbering in the metadata
+fn metadata_v1_to_v2(table: &mut toml::Table) -> Result<(), SaveError> {
+    let blocks = V1_BLOCKS
+        .iter()
+        .enumerate()
+        .map(|(id, &name)| (name.to_string(), toml::Value::Integer(id as i64)))
+        .collect();
+    table.insert("blocks".to_string(), toml::Value::Table(blocks));
+    Ok(())
+}
+
+/// Format 1 marked the stored sections with a bit mask counted up from
+/// y = -64; format 2 gives each section its own height, so the world's
+/// vertical range can change without breaking saves
+fn chunk_v1_to_v2(bytes: &[u8]) -> Result<Vec<u8>, SaveError> {
+    /// Height in sections of the lowest section in format 1
+    const V1_LOWEST_SECTION: i32 = -4;
+    const V1_SECTION_VOLUME: usize = 16 * 16 * 16;
+
+    let mut reader = Reader::new(bytes);
+    reader.u32()?;
+    let (x, z, mask) = (reader.u32()?, reader.u32()?, reader.u32()?);
+    let mut upgraded = Vec::with_capacity(bytes.len() + 32);
+    upgraded.extend_from_slice(&2u32.to_le_bytes());
+    upgraded.extend_from_slice(&x.to_le_bytes());
+    upgraded.extend_from_slice(&z.to_le_bytes());
+    upgraded.push(mask.count_ones() as u8);
+    for index in (0..32).filter(|index| mask & 1 << index != 0) {
+        upgraded.push((V1_LOWEST_SECTION + index) as i8 as u8);
+        // The palette and indices are unchanged
+        let start = reader.position();
+        let palette = reader.u16()? as usize;
+        reader.skip(palette * 2)?;
+        if palette > 1 {
+            reader.skip(V1_SECTION_VOLUME * if palette <= 256 { 1 } else { 2 })?;
+        }
+        upgraded.extend_from_slice(&bytes[start..reader.position()]);
+    }
+    if !reader.is_at_end() {
+        return Err(SaveError::Corrupt("trailing bytes after chunk data".to_string()));
+    }
+    Ok(upgraded)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::*;
+    use crate::block::BlockType;
+    use crate::chunk::{Chunk, MIN_Y};
+    use crate::save::{WorldSave, METADATA_FILE};
+
+    /// The chunks stored in every fixture world
+    const FIXTURE_CHUNKS: [(i32, i32); 3] = [(0, 0), (3, 2), (-1, -1)];
+
+    /// Rebuilds a fixture chunk; must stay identical to the code that wrote
+    /// the fixtures
+    fn fixture_chunk(position: (i32, i32)) -> Chunk {
+        let mut chunk = Chunk::new(position);
+        let seed = (position.0 * 31 + position.1 * 17).rem_euclid(97) as usize;
+        for x in 0..16 {
+            for z in 0..16 {
+                let height = (20 + (x * 3 + z * 5 + seed) % 12) as i32;
+                for y in MIN_Y..height {
+                    let block = if y == MIN_Y {
+                        BlockType::Bedrock
+                    } else if (x as i32 + y + z as i32).rem_euclid(23) == 0 {
+                        BlockType::DiamondOre
+                    } else if y < height - 3 {
+                        BlockType::Stone
+                    } else {
+                        BlockType::Dirt
+                    };
+                    chunk.set_block_at(x, y, z, block);
+                }
+                let top = BlockType::ALL[(x + z * 16 + seed) % BlockType::ALL.len()];
+                chunk.set_block_at(x, height, z, top);
+            }
+        }
+        chunk
+    }
+
+    fn copy_dir(from: &Path, to: &Path) {
+        std::fs::create_dir_all(to).unwrap();
+        for entry in std::fs::read_dir(from).unwrap() {
+            let entry = entry.unwrap();
+            if entry.file_type().unwrap().is_dir() {
+                copy_dir(&entry.path(), &to.join(entry.file_name()));
+            } else {
+                std::fs::copy(entry.path(), to.join(entry.file_name())).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn test_fixture_worlds_from_every_version_load() {
+        let fixtures = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+        for version in 1..FORMAT_VERSION {
+            let fixture = fixtures.join(format!("v{}", version));
+            assert!(fixture.is_dir(), "no fixture world for format version {}", version);
+
+            // Opening upgrades the metadata, so work on a copy
+            let dir = std::env::temp_dir()
+                .join(format!("migrate-test-v{}-{}", version, std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            copy_dir(&fixture, &dir);
+
+            let (save, metadata) = WorldSave::open(&dir).unwrap();
+            assert_eq!(metadata.format_version, FORMAT_VERSION);
+            assert_eq!((metadata.seed, metadata.spawn), (2024, [8, 40, -8]));
+            for position in FIXTURE_CHUNKS {
+                let chunk = save.load_chunk(position).unwrap().expect("missing fixture chunk");
+                assert!(chunk == fixture_chunk(position), "v{} chunk {:?}", version, position);
+                assert!(!chunk.is_modified());
+            }
+            assert!(save.load_chunk((1, 0)).unwrap().is_none());
+
+            // The upgraded metadata was written back, and old and new chunks
+            // live side by side in the same region
+            let text = std::fs::read_to_string(dir.join(METADATA_FILE)).unwrap();
+            assert!(text.contains(&format!("format_version = {}", FORMAT_VERSION)));
+            let mut edited = fixture_chunk((3, 2));
+            edited.set_block(0, 100, 0, BlockType::Planks);
+            save.save_chunk(&edited).unwrap();
+            let (save, _) = WorldSave::open(&dir).unwrap();
+            assert!(save.load_chunk((3, 2)).unwrap().unwrap() == edited);
+            assert!(save.load_chunk((0, 0)).unwrap().unwrap() == fixture_chunk((0, 0)));
+            std::fs::remove_dir_all(&dir).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_unknown_versions_are_refused() {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        assert!(matches!(migrate_chunk(bytes), Err(SaveError::UnsupportedVersion(0))));
+
+        let mut table = toml::Table::new();
+        assert!(matches!(migrate_metadata(&mut table), Err(SaveError::Corrupt(_))));
+        table.insert("format_version".to_string(), toml::Value::Integer(99));
+        assert!(matches!(migrate_metadata(&mut table), Err(SaveError::UnsupportedVersion(99))));
+    }
+}
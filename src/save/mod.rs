@@ -0,0 +1,338 @@
+//! Saving and loading worlds
+//!
+//! A save is a directory holding `level.toml`, with the world's metadata and
+//! generator settings, and a `region` directory of region files. Each region
+//! file groups 32x32 chunks behind an offset table, with every chunk stored
+//! as its own zlib-compressed payload. Files are replaced atomically, so a
+//! crash mid-save leaves the previous version intact.
+//!
+//! Chunks store blocks under the numeric IDs in the world's [`BlockIds`]
+//! table. Saves from older format versions are upgraded as they are read.
+
+mod codec;
+mod ids;
+mod manager;
+mod migrate;
+mod region;
+
+pub use codec::{decode_chunk, encode_chunk};
+pub use ids::BlockIds;
+pub use manager::SaveManager;
+pub use migrate::{migrate_chunk, migrate_metadata};
+pub use region::{RegionFile, REGION_CHUNKS};
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::chunk::Chunk;
+use crate::worldgen::GeneratorSettings;
+
+/// Version of the save format written by this build
+pub const FORMAT_VERSION: u32 = 2;
+/// Name of the metadata file at the root of a save
+pub const METADATA_FILE: &str = "level.toml";
+/// Directory inside a save holding the region files
+pub const REGION_DIR: &str = "region";
+
+/// Errors from reading or writing a save
+#[derive(Debug)]
+pub enum SaveError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    Serialize(toml::ser::Error),
+    /// Data that does not follow the save format
+    Corrupt(String),
+    /// Data written by a newer or unknown format version
+    UnsupportedVersion(u32),
+    /// Saved chunk that could not be loaded
+    ChunkLoad((i32, i32), Box<SaveError>),
+}
+
+impl std::fmt::Display for SaveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SaveError::Io(e) => write!(f, "I/O error: {}", e),
+            SaveError::Parse(e) => write!(f, "invalid world metadata: {}", e),
+            SaveError::Serialize(e) => write!(f, "failed to serialize world metadata: {}", e),
+            SaveError::Corrupt(reason) => write!(f, "corrupt save data: {}", reason),
+            SaveError::UnsupportedVersion(version) => {
+                write!(f, "unsupported save format version {}", version)
+            }
+            SaveError::ChunkLoad(position, e) => {
+                write!(f, "failed to load chunk {:?}: {}", position, e)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+impl From<io::Error> for SaveError {
+    fn from(e: io::Error) -> Self {
+        SaveError::Io(e)
+    }
+}
+
+/// Contents of `level.toml`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorldMetadata {
+    /// Save format version the world was written with
+    pub format_version: u32,
+    /// World seed; takes precedence over the seed in `generator`
+    pub seed: u32,
+    /// Block the player spawns at
+    pub spawn: [i32; 3],
+    /// Settings for generating chunks that were never saved
+    pub generator: GeneratorSettings,
+    /// Numeric ID of each block in the world's chunks, by block name
+    pub blocks: BTreeMap<String, u16>,
+}
+
+impl WorldMetadata {
+    pub fn new(generator: GeneratorSettings, spawn: [i32; 3]) -> Self {
+        Self {
+            format_version: FORMAT_VERSION,
+            seed: generator.seed,
+            spawn,
+            generator,
+            blocks: BlockIds::default().names().clone(),
+        }
+    }
+
+    /// Generator settings with the world seed applied
+    pub fn generator_settings(&self) -> GeneratorSettings {
+        GeneratorSettings { seed: self.seed, ..self.generator.clone() }
+    }
+}
+
+/// A world save directory
+pub struct WorldSave {
+    dir: PathBuf,
+    ids: BlockIds,
+}
+
+impl WorldSave {
+    /// Creates a save in `dir`, or overwrites the metadata of the one there
+    pub fn create(dir: impl AsRef<Path>, metadata: &WorldMetadata) -> Result<Self, SaveError> {
+        let save = Self {
+            dir: dir.as_ref().to_path_buf(),
+            ids: BlockIds::from_names(&metadata.blocks)?,
+        };
+        std::fs::create_dir_all(save.dir.join(REGION_DIR))?;
+        save.save_metadata(metadata)?;
+        Ok(save)
+    }
+
+    /// Opens the save in `dir` and reads its metadata
+    ///
+    /// Metadata from an older format version, or without IDs for blocks added
+    /// since the world was last played, is upgraded and written back.
+    pub fn open(dir: impl AsRef<Path>) -> Result<(Self, WorldMetadata), SaveError> {
+        let dir = dir.as_ref().to_path_buf();
+        let (mut metadata, migrated) = read_metadata(&dir)?;
+        let save = Self { ids: BlockIds::from_names(&metadata.blocks)?, dir };
+        if migrated || save.ids.names() != &metadata.blocks {
+            metadata.blocks = save.ids.names().clone();
+            save.save_metadata(&metadata)?;
+        }
+        Ok((save, metadata))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// IDs the save's chunks store blocks under
+    pub fn block_ids(&self) -> &BlockIds {
+        &self.ids
+    }
+
+    pub fn load_metadata(&self) -> Result<WorldMetadata, SaveError> {
+        Ok(read_metadata(&self.dir)?.0)
+    }
+
+    /// Writes `metadata`, always with the save's own block ID table
+    pub fn save_metadata(&self, metadata: &WorldMetadata) -> Result<(), SaveError> {
+        let metadata = WorldMetadata { blocks: self.ids.names().clone(), ..metadata.clone() };
+        let text = toml::to_string_pretty(&metadata).map_err(SaveError::Serialize)?;
+        write_atomic(self.dir.join(METADATA_FILE), text.as_bytes())?;
+        Ok(())
+    }
+
+    /// Writes a chunk to its region file
+    pub fn save_chunk(&self, chunk: &Chunk) -> Result<(), SaveError> {
+        self.save_chunks([chunk])
+    }
+
+    /// Writes several chunks, rewriting each region file they touch once
+    ///
+    /// Region files are read, updated and replaced whole, so only one thread
+    /// should save into a world at a time.
+    pub fn save_chunks<'a>(
+        &self,
+        chunks: impl IntoIterator<Item = &'a Chunk>,
+    ) -> Result<(), SaveError> {
+        let mut regions: HashMap<(i32, i32), Vec<&Chunk>> = HashMap::new();
+        for chunk in chunks {
+            regions.entry(RegionFile::containing(chunk.position)).or_default().push(chunk);
+        }
+        for (position, chunks) in regions {
+            let path = self.region_path(position);
+            let mut region = RegionFile::read(&path, position)?
+                .unwrap_or_else(|| RegionFile::new(position));
+            for chunk in chunks {
+                region.set_payload(chunk.position, encode_chunk(chunk, &self.ids));
+            }
+            region.write(&path)?;
+        }
+        Ok(())
+    }
+
+    /// Reads a chunk from its region file, or `None` if it was never saved
+    pub fn load_chunk(&self, position: (i32, i32)) -> Result<Option<Chunk>, SaveError> {
+        let path = self.region_path(RegionFile::containing(position));
+        match RegionFile::read_payload(&path, position)? {
+            Some(payload) => {
+                let chunk = decode_chunk(&payload, &self.ids)?;
+                if chunk.position != position {
+                    return Err(SaveError::Corrupt(format!(
+                        "chunk {:?} stored in the slot for {:?}",
+                        chunk.position, position
+                    )));
+                }
+                Ok(Some(chunk))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn region_path(&self, position: (i32, i32)) -> PathBuf {
+        self.dir.join(REGION_DIR).join(RegionFile::file_name(position))
+    }
+}
+
+/// Reads and upgrades the metadata of the save in `dir`, returning whether it
+/// came from an older format version
+fn read_metadata(dir: &Path) -> Result<(WorldMetadata, bool), SaveError> {
+    let text = std::fs::read_to_string(dir.join(METADATA_FILE))?;
+    let mut table: toml::Table = toml::from_str(&text).map_err(SaveError::Parse)?;
+    let migrated = migrate_metadata(&mut table)?;
+    let metadata = toml::Value::Table(table).try_into().map_err(SaveError::Parse)?;
+    Ok((metadata, migrated))
+}
+
+/// Replaces the file at `path` so that it holds either its old or its new
+/// contents, even if the process dies or the power fails midway
+///
+/// The data goes to a temporary file next to it, which is flushed to disk and
+/// then renamed over the original.
+pub fn write_atomic(path: impl AsRef<Path>, bytes: &[u8]) -> io::Result<()> {
+    let path = path.as_ref();
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+    let temp = PathBuf::from(temp);
+    {
+        let mut file = File::create(&temp)?;
+        file.write_all(bytes)?;
+        file.sync_all()?;
+    }
+    std::fs::rename(&temp, path)?;
+    // Persist the rename itself; directories cannot be opened on every
+    // platform, so this is best effort
+    if let Some(dir) = path.parent() {
+        if let Ok(dir) = File::open(dir) {
+            let _ = dir.sync_all();
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::BlockType;
+    use crate::worldgen::{TerrainGenerator, WorldGenerator};
+
+    fn temp_save(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("save-test-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_metadata_round_trip() {
+        let dir = temp_save("metadata");
+        let settings = GeneratorSettings { seed: 77, sea_level: 20, ..Default::default() };
+        let metadata = WorldMetadata::new(settings.clone(), [4, 40, -9]);
+        WorldSave::create(&dir, &metadata).unwrap();
+
+        let (save, loaded) = WorldSave::open(&dir).unwrap();
+        assert_eq!(loaded, metadata);
+        assert_eq!(loaded.generator_settings(), settings);
+        assert_eq!(save.dir(), dir.as_path());
+
+        // Saves from a newer build are refused rather than misread
+        let newer = WorldMetadata { format_version: FORMAT_VERSION + 1, ..metadata };
+        save.save_metadata(&newer).unwrap();
+        assert!(matches!(WorldSave::open(&dir), Err(SaveError::UnsupportedVersion(_))));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_chunk_round_trip() {
+        let dir = temp_save("chunks");
+        let settings = GeneratorSettings::default();
+        let save = WorldSave::create(&dir, &WorldMetadata::new(settings.clone(), [0, 64, 0]))
+            .unwrap();
+        let generator = TerrainGenerator::new(settings);
+
+        // Chunks on both sides of a region border, plus an edited one
+        let positions = [(0, 0), (31, -1), (32, -1), (-1, -33)];
+        let chunks: Vec<Chunk> = positions
+            .iter()
+            .map(|&position| {
+                let mut chunk = Chunk::new(position);
+                generator.generate_chunk(&mut chunk);
+                chunk
+            })
+            .collect();
+        save.save_chunks(&chunks).unwrap();
+        let mut edited = chunks[0].clone();
+        edited.set_block_at(3, -40, 3, BlockType::DiamondOre);
+        edited.set_block(3, 200, 3, BlockType::Planks);
+        save.save_chunk(&edited).unwrap();
+
+        assert!(save.load_chunk((0, 0)).unwrap().unwrap() == edited);
+        for chunk in &chunks[1..] {
+            assert!(save.load_chunk(chunk.position).unwrap().as_ref() == Some(chunk));
+        }
+        assert!(save.load_chunk((1, 0)).unwrap().is_none());
+        assert!(save.load_chunk((500, 500)).unwrap().is_none());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_corrupt_region_is_an_error() {
+        let dir = temp_save("corrupt");
+        let save = WorldSave::create(&dir, &WorldMetadata::new(Default::default(), [0; 3]))
+            .unwrap();
+        let mut chunk = Chunk::new((2, 2));
+        chunk.set_block(0, 0, 0, BlockType::Bedrock);
+        save.save_chunk(&chunk).unwrap();
+
+        let path = save.region_path((0, 0));
+        let mut bytes = std::fs::read(&path).unwrap();
+        let end = bytes.len();
+        bytes[end - 4..].fill(0xff);
+        std::fs::write(&path, &bytes).unwrap();
+        assert!(matches!(save.load_chunk((2, 2)), Err(SaveError::Corrupt(_))));
+
+        std::fs::write(&path, b"not a region").unwrap();
+        assert!(matches!(save.load_chunk((2, 2)), Err(SaveError::Corrupt(_))));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
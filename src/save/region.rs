@@ -0,0 +1,210 @@
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use super::{write_atomic, SaveError, FORMAT_VERSION};
+
+/// Width of a region in chunks
+pub const REGION_CHUNKS: i32 = 32;
+/// Chunk slots in a region
+const SLOTS: usize = (REGION_CHUNKS * REGION_CHUNKS) as usize;
+/// Magic bytes at the start of a region file
+const MAGIC: &[u8; 4] = b"VXRG";
+/// Magic and version, then an offset and a length per slot
+const HEADER_LEN: usize = 8 + SLOTS * 8;
+
+/// A 32x32 group of chunks stored in one file
+///
+/// The file starts with a table giving each slot's byte offset and length,
+/// followed by the chunks' compressed payloads. Empty slots have a length of
+/// zero. Single chunks can be read through the table without loading the
+/// rest of the file.
+pub struct RegionFile {
+    position: (i32, i32),
+    payloads: Vec<Option<Vec<u8>>>,
+}
+
+impl RegionFile {
+    /// An empty region
+    pub fn new(position: (i32, i32)) -> Self {
+        Self {
+            position,
+            payloads: vec![None; SLOTS],
+        }
+    }
+
+    /// Region holding the chunk at `chunk`
+    pub fn containing(chunk: (i32, i32)) -> (i32, i32) {
+        (chunk.0.div_euclid(REGION_CHUNKS), chunk.1.div_euclid(REGION_CHUNKS))
+    }
+
+    pub fn file_name(position: (i32, i32)) -> String {
+        format!("r.{}.{}.vxr", position.0, position.1)
+    }
+
+    pub fn position(&self) -> (i32, i32) {
+        self.position
+    }
+
+    /// Number of chunks stored in the region
+    pub fn chunk_count(&self) -> usize {
+        self.payloads.iter().filter(|payload| payload.is_some()).count()
+    }
+
+    /// Compressed payload of a chunk in this region
+    pub fn payload(&self, chunk: (i32, i32)) -> Option<&[u8]> {
+        self.payloads[self.slot(chunk)].as_deref()
+    }
+
+    /// Stores the compressed payload of a chunk in this region
+    pub fn set_payload(&mut self, chunk: (i32, i32), payload: Vec<u8>) {
+        let slot = self.slot(chunk);
+        self.payloads[slot] = Some(payload);
+    }
+
+    /// Reads a whole region file, or `None` if it does not exist
+    pub fn read(path: impl AsRef<Path>, position: (i32, i32)) -> Result<Option<Self>, SaveError> {
+        let bytes = match std::fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let table = read_table(&bytes)?;
+        let mut region = Self::new(position);
+        for (slot, &(offset, length)) in table.iter().enumerate() {
+            if length > 0 {
+                let range = payload_range(offset, length, bytes.len())?;
+                region.payloads[slot] = Some(bytes[range].to_vec());
+            }
+        }
+        Ok(Some(region))
+    }
+
+    /// Reads only the payload of one chunk from a region file
+    ///
+    /// Returns `None` if the file does not exist or the chunk is not in it.
+    pub fn read_payload(
+        path: impl AsRef<Path>,
+        chunk: (i32, i32),
+    ) -> Result<Option<Vec<u8>>, SaveError> {
+        let mut file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let file_len = file.metadata()?.len() as usize;
+        let mut header = vec![0; HEADER_LEN.min(file_len)];
+        file.read_exact(&mut header)?;
+        let (offset, length) = read_table(&header)?[slot_of(chunk)];
+        if length == 0 {
+            return Ok(None);
+        }
+        let range = payload_range(offset, length, file_len)?;
+        let mut payload = vec![0; range.len()];
+        file.seek(SeekFrom::Start(range.start as u64))?;
+        file.read_exact(&mut payload)?;
+        Ok(Some(payload))
+    }
+
+    /// Writes the region to `path`, atomically replacing any file there
+    pub fn write(&self, path: impl AsRef<Path>) -> Result<(), SaveError> {
+        write_atomic(path, &self.to_bytes())?;
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let body: usize = self.payloads.iter().flatten().map(Vec::len).sum();
+        let mut bytes = Vec::with_capacity(HEADER_LEN + body);
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        let mut offset = HEADER_LEN;
+        for payload in &self.payloads {
+            let length = payload.as_ref().map_or(0, Vec::len);
+            let start = if length > 0 { offset } else { 0 };
+            bytes.extend_from_slice(&(start as u32).to_le_bytes());
+            bytes.extend_from_slice(&(length as u32).to_le_bytes());
+            offset += length;
+        }
+        for payload in self.payloads.iter().flatten() {
+            bytes.extend_from_slice(payload);
+        }
+        bytes
+    }
+
+    fn slot(&self, chunk: (i32, i32)) -> usize {
+        assert_eq!(Self::containing(chunk), self.position, "chunk outside region");
+        slot_of(chunk)
+    }
+}
+
+/// Index of a chunk within its region's table
+fn slot_of(chunk: (i32, i32)) -> usize {
+    let x = chunk.0.rem_euclid(REGION_CHUNKS) as usize;
+    let z = chunk.1.rem_euclid(REGION_CHUNKS) as usize;
+    z * REGION_CHUNKS as usize + x
+}
+
+/// Checks the header and returns each slot's offset and length
+fn read_table(bytes: &[u8]) -> Result<Vec<(usize, usize)>, SaveError> {
+    if bytes.len() < HEADER_LEN || &bytes[0..4] != MAGIC {
+        return Err(SaveError::Corrupt("not a region file".to_string()));
+    }
+    let u32_at = |i: usize| u32::from_le_bytes(bytes[i..i + 4].try_into().unwrap());
+    let version = u32_at(4);
+    if version > FORMAT_VERSION {
+        return Err(SaveError::UnsupportedVersion(version));
+    }
+    Ok((0..SLOTS)
+        .map(|slot| (u32_at(8 + slot * 8) as usize, u32_at(12 + slot * 8) as usize))
+        .collect())
+}
+
+fn payload_range(
+    offset: usize,
+    length: usize,
+    file_len: usize,
+) -> Result<std::ops::Range<usize>, SaveError> {
+    if offset < HEADER_LEN || offset + length > file_len {
+        return Err(SaveError::Corrupt(format!(
+            "chunk data at {}..{} lies outside the region file",
+            offset,
+            offset + length
+        )));
+    }
+    Ok(offset..offset + length)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_region_bytes_round_trip() {
+        let dir = std::env::temp_dir().join(format!("region-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(RegionFile::file_name((-1, 0)));
+
+        let mut region = RegionFile::new((-1, 0));
+        region.set_payload((-32, 0), vec![1, 2, 3]);
+        region.set_payload((-1, 31), vec![4; 100]);
+        region.write(&path).unwrap();
+
+        let loaded = RegionFile::read(&path, (-1, 0)).unwrap().unwrap();
+        assert_eq!(loaded.chunk_count(), 2);
+        assert_eq!(loaded.payload((-32, 0)), Some(&[1, 2, 3][..]));
+        assert_eq!(loaded.payload((-1, 31)), Some(&[4; 100][..]));
+        assert_eq!(loaded.payload((-2, 5)), None);
+        assert_eq!(RegionFile::read_payload(&path, (-1, 31)).unwrap(), Some(vec![4; 100]));
+        assert_eq!(RegionFile::read_payload(&path, (-2, 5)).unwrap(), None);
+        assert!(RegionFile::read(dir.join("missing"), (0, 0)).unwrap().is_none());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_containing_region() {
+        assert_eq!(RegionFile::containing((0, 31)), (0, 0));
+        assert_eq!(RegionFile::containing((32, -1)), (1, -1));
+        assert_eq!(RegionFile::containing((-33, -32)), (-2, -1));
+        assert_eq!(slot_of((-1, -1)), SLOTS - 1);
+    }
+}
@@ -7,6 +7,13 @@ struct CameraUniform {
 @group(0) @binding(0)
 var<uniform> camera: CameraUniform;
 
+struct ChunkUniform {
+    offset: vec4<f32>,
+};
+
+@group(1) @binding(0)
+var<uniform> chunk: ChunkUniform;
+
 struct VertexInput {
     @location(0) position: vec3<f32>,
     @location(1) color: vec3<f32>,
@@ -23,7 +30,8 @@ fn vs_main(
 ) -> VertexOutput {
     var out: VertexOutput;
     out.color = model.color;
-    out.clip_position = camera.view_proj * vec4<f32>(model.position, 1.0);
+    let world_position = model.position + chunk.offset.xyz;
+    out.clip_position = camera.view_proj * vec4<f32>(world_position, 1.0);
     return out;
 }
 
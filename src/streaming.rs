@@ -0,0 +1,429 @@
+use std::collections::{HashSet, VecDeque};
+use std::sync::Arc;
+
+use glam::Vec3;
+
+use crate::chunk::Chunk;
+use crate::jobs::{Job, JobResult, MeshInput, WorkerPool};
+use crate::mesh::{ChunkMesh, MeshingMode};
+use crate::world::World;
+use crate::worldgen::WorldGenerator;
+
+/// Settings controlling how chunks are streamed around the camera
+#[derive(Debug, Clone)]
+pub struct StreamingConfig {
+    /// Radius in chunks around the camera that is kept loaded
+    pub render_distance: i32,
+    /// Maximum number of generated chunks inserted into the world per update
+    pub generation_budget: usize,
+    /// Maximum number of meshes handed to the renderer per update
+    pub mesh_budget: usize,
+    pub meshing_mode: MeshingMode,
+    /// Number of background threads generating and meshing chunks
+    pub worker_threads: usize,
+}
+
+impl Default for StreamingConfig {
+    fn default() -> Self {
+        Self {
+            render_distance: 8,
+            generation_budget: 4,
+            mesh_budget: 4,
+            meshing_mode: MeshingMode::Greedy,
+            worker_threads: std::thread::available_parallelism()
+                .map(|n| n.get().saturating_sub(1))
+                .unwrap_or(1)
+                .max(1),
+        }
+    }
+}
+
+/// Changes the renderer has to apply after a streaming update
+#[derive(Debug, Default)]
+pub struct StreamingUpdate {
+    /// New meshes for chunks that were generated or changed
+    pub meshes: Vec<((i32, i32), ChunkMesh)>,
+    /// Chunks that were evicted and whose meshes should be dropped
+    pub unloaded: Vec<(i32, i32)>,
+    /// Evicted chunks with unsaved changes, to hand to a save
+    pub evicted: Vec<Chunk>,
+}
+
+/// Loads, meshes and evicts chunks as the camera moves
+///
+/// Generation and meshing run on a [`WorkerPool`]; finished work is folded
+/// back into the world a few chunks per update.
+pub struct ChunkStreamer {
+    config: StreamingConfig,
+    pool: WorkerPool,
+    /// Chunk whose surroundings the queued jobs were prioritised for
+    center: Option<(i32, i32)>,
+    /// Chunks being generated or waiting in `generated` to be inserted
+    pending_generation: HashSet<(i32, i32)>,
+    pending_meshes: HashSet<(i32, i32)>,
+    generated: VecDeque<Chunk>,
+    meshed: VecDeque<((i32, i32), ChunkMesh)>,
+}
+
+impl ChunkStreamer {
+    /// Creates a streamer filling new chunks with `generator`
+    pub fn new(config: StreamingConfig, generator: Arc<dyn WorldGenerator>) -> Self {
+        Self {
+            pool: WorkerPool::new(config.worker_threads, generator),
+            config,
+            center: None,
+            pending_generation: HashSet::new(),
+            pending_meshes: HashSet::new(),
+            generated: VecDeque::new(),
+            meshed: VecDeque::new(),
+        }
+    }
+
+    pub fn config(&self) -> &StreamingConfig {
+        &self.config
+    }
+
+    /// Chunk coordinates containing a world position
+    pub fn chunk_at(position: Vec3) -> (i32, i32) {
+        World::world_to_chunk(position.x.floor() as i32, position.z.floor() as i32).0
+    }
+
+    /// Returns true if `position` lies within the render distance of `center`
+    pub fn in_range(&self, center: (i32, i32), position: (i32, i32)) -> bool {
+        distance_squared(center, position) <= self.config.render_distance.pow(2)
+    }
+
+    /// Chunk coordinates that should be loaded around `center`, nearest first
+    pub fn wanted_chunks(&self, center: (i32, i32)) -> Vec<(i32, i32)> {
+        let r = self.config.render_distance;
+        let mut wanted: Vec<(i32, i32)> = (-r..=r)
+            .flat_map(|dx| (-r..=r).map(move |dz| (center.0 + dx, center.1 + dz)))
+            .filter(|&position| self.in_range(center, position))
+            .collect();
+        wanted.sort_by_key(|&position| (distance_squared(center, position), position));
+        wanted
+    }
+
+    /// Advances streaming by one frame
+    ///
+    /// Evicts chunks outside the render distance, queues generation of the
+    /// missing chunks and meshing of dirty ones, and applies finished work
+    /// within the per-frame budgets, nearest chunks first.
+    pub fn update(&mut self, world: &mut World, camera_position: Vec3) -> StreamingUpdate {
+        let center = Self::chunk_at(camera_position);
+        let mut update = StreamingUpdate::default();
+
+        if self.center != Some(center) {
+            self.center = Some(center);
+            self.pool.reprioritize(|position| distance_squared(center, position));
+        }
+
+        let evicted: Vec<(i32, i32)> = world
+            .chunk_positions()
+            .filter(|&position| !self.in_range(center, position))
+            .collect();
+        for position in evicted {
+            if let Some(chunk) = world.remove_chunk(position) {
+                if chunk.is_modified() {
+                    update.evicted.push(chunk);
+                }
+            }
+            update.unloaded.push(position);
+        }
+        let abandoned: Vec<(i32, i32)> = self
+            .pending_generation
+            .union(&self.pending_meshes)
+            .copied()
+            .filter(|&position| !self.in_range(center, position))
+            .collect();
+        for position in abandoned {
+            self.pool.cancel(position);
+            self.pending_generation.remove(&position);
+            self.pending_meshes.remove(&position);
+        }
+
+        for position in self.wanted_chunks(center) {
+            if !world.is_loaded(position) && self.pending_generation.insert(position) {
+                self.pool
+                    .submit(Job::Generate { position }, distance_squared(center, position));
+            }
+        }
+
+        self.collect_results(world);
+
+        let radius_squared = self.config.render_distance.pow(2);
+        self.generated
+            .retain(|chunk| distance_squared(center, chunk.position) <= radius_squared);
+        self.generated
+            .make_contiguous()
+            .sort_by_key(|chunk| distance_squared(center, chunk.position));
+        for _ in 0..self.config.generation_budget {
+            let Some(chunk) = self.generated.pop_front() else { break };
+            self.pending_generation.remove(&chunk.position);
+            world.insert_chunk(chunk);
+        }
+
+        // Wait for in-range neighbours so border faces are only meshed once
+        let dirty: Vec<(i32, i32)> = world
+            .dirty_chunks()
+            .filter(|position| !self.pending_meshes.contains(position))
+            .filter(|&position| self.ready_to_mesh(world, center, position))
+            .collect();
+        for position in dirty {
+            if let Some(chunk) = world.get_chunk(position) {
+                let input = MeshInput::new(chunk, &world.neighbors(position), self.config.meshing_mode);
+                self.pool
+                    .submit(Job::Mesh(Box::new(input)), distance_squared(center, position));
+                self.pending_meshes.insert(position);
+            }
+            world.clear_dirty(position);
+        }
+
+        self.meshed.retain(|(position, _)| world.is_loaded(*position));
+        self.meshed
+            .make_contiguous()
+            .sort_by_key(|(position, _)| distance_squared(center, *position));
+        for _ in 0..self.config.mesh_budget {
+            let Some(mesh) = self.meshed.pop_front() else { break };
+            update.meshes.push(mesh);
+        }
+
+        update
+    }
+
+    /// Returns true when no jobs are running and no results are waiting to be applied
+    pub fn is_idle(&self) -> bool {
+        self.pending_generation.is_empty()
+            && self.pending_meshes.is_empty()
+            && self.generated.is_empty()
+            && self.meshed.is_empty()
+    }
+
+    /// Blocks until the worker pool has finished all queued jobs
+    pub fn wait_for_jobs(&self) {
+        self.pool.wait_idle();
+    }
+
+    /// Moves finished jobs from the pool into the local queues
+    fn collect_results(&mut self, world: &World) {
+        while let Some(result) = self.pool.try_recv() {
+            match result {
+                JobResult::Generated(chunk) => {
+                    // Results of cancelled jobs can still arrive, so only the
+                    // first one for a position still wanted is kept
+                    let position = chunk.position;
+                    if self.pending_generation.contains(&position)
+                        && !world.is_loaded(position)
+                        && !self.generated.iter().any(|queued| queued.position == position)
+                    {
+                        self.generated.push_back(*chunk);
+                    }
+                }
+                JobResult::Meshed { position, mesh } => {
+                    if self.pending_meshes.remove(&position) && world.is_loaded(position) {
+                        // Replace any older mesh still waiting for upload
+                        self.meshed.retain(|(queued, _)| *queued != position);
+                        self.meshed.push_back((position, mesh));
+                    }
+                }
+            }
+        }
+    }
+
+    /// A chunk is ready once every neighbour inside the render distance is loaded
+    fn ready_to_mesh(&self, world: &World, center: (i32, i32), position: (i32, i32)) -> bool {
+        [(1, 0), (-1, 0), (0, 1), (0, -1)].iter().all(|&(dx, dz)| {
+            let neighbor = (position.0 + dx, position.1 + dz);
+            world.is_loaded(neighbor) || !self.in_range(center, neighbor)
+        })
+    }
+}
+
+fn distance_squared(a: (i32, i32), b: (i32, i32)) -> i32 {
+    (a.0 - b.0).pow(2) + (a.1 - b.1).pow(2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    use crate::block::BlockType;
+    use crate::worldgen::TerrainGenerator;
+
+    type ChunkSet = HashSet<(i32, i32)>;
+
+    fn streamer(render_distance: i32) -> ChunkStreamer {
+        ChunkStreamer::new(
+            StreamingConfig {
+                render_distance,
+                generation_budget: 3,
+                mesh_budget: 3,
+                worker_threads: 2,
+                ..Default::default()
+            },
+            Arc::new(TerrainGenerator::default()),
+        )
+    }
+
+    /// Runs updates until nothing is left to generate or mesh, returning the
+    /// meshed chunks and the evicted ones
+    fn settle(
+        streamer: &mut ChunkStreamer,
+        world: &mut World,
+        camera: Vec3,
+    ) -> (ChunkSet, Vec<(i32, i32)>) {
+        let mut meshed = HashSet::new();
+        let mut unloaded = Vec::new();
+        for _ in 0..10_000 {
+            let update = streamer.update(world, camera);
+            meshed.extend(update.meshes.iter().map(|(position, _)| *position));
+            unloaded.extend(update.unloaded);
+            if streamer.is_idle() && world.dirty_chunks().next().is_none() {
+                return (meshed, unloaded);
+            }
+            streamer.wait_for_jobs();
+        }
+        panic!("streaming did not settle");
+    }
+
+    fn loaded(world: &World) -> HashSet<(i32, i32)> {
+        world.chunk_positions().collect()
+    }
+
+    #[test]
+    fn test_loads_circle_around_camera() {
+        let mut streamer = streamer(3);
+        let mut world = World::new();
+        let camera = Vec3::new(8.0, 40.0, 8.0);
+        let (meshed, _) = settle(&mut streamer, &mut world, camera);
+
+        let expected: HashSet<(i32, i32)> = streamer.wanted_chunks((0, 0)).into_iter().collect();
+        assert_eq!(expected.len(), 29);
+        assert_eq!(loaded(&world), expected);
+        assert_eq!(meshed, expected);
+    }
+
+    #[test]
+    fn test_budget_limits_work_per_update() {
+        let mut streamer = streamer(4);
+        let mut world = World::new();
+        streamer.update(&mut world, Vec3::ZERO);
+        streamer.wait_for_jobs();
+        let update = streamer.update(&mut world, Vec3::ZERO);
+        assert_eq!(world.chunk_count(), 3);
+        assert!(update.meshes.is_empty());
+
+        // The first chunks generated are the ones nearest the camera
+        assert!(world.is_loaded((0, 0)));
+        for position in world.chunk_positions() {
+            assert!(distance_squared((0, 0), position) <= 1);
+        }
+    }
+
+    #[test]
+    fn test_camera_path_evicts_and_loads() {
+        let mut streamer = streamer(2);
+        let mut world = World::new();
+        settle(&mut streamer, &mut world, Vec3::ZERO);
+
+        // Cross chunk borders on both axes, ending deep in negative coordinates
+        let path = [
+            Vec3::new(20.0, 30.0, -3.0),
+            Vec3::new(70.0, 30.0, -40.0),
+            Vec3::new(-100.0, 30.0, -100.0),
+        ];
+        for camera in path {
+            let (meshed, unloaded) = settle(&mut streamer, &mut world, camera);
+
+            let center = ChunkStreamer::chunk_at(camera);
+            let expected: HashSet<(i32, i32)> = streamer.wanted_chunks(center).into_iter().collect();
+            assert_eq!(loaded(&world), expected);
+            assert!(meshed.is_subset(&expected));
+            assert!(!unloaded.is_empty());
+            assert!(unloaded.iter().all(|&position| !streamer.in_range(center, position)));
+        }
+        assert_eq!(ChunkStreamer::chunk_at(Vec3::new(-100.0, 0.0, -100.0)), (-7, -7));
+    }
+
+    /// Places one block per chunk and counts how often each chunk was generated
+    #[derive(Default)]
+    struct CountingGenerator {
+        calls: Mutex<HashMap<(i32, i32), usize>>,
+    }
+
+    impl WorldGenerator for CountingGenerator {
+        fn generate_chunk(&self, chunk: &mut Chunk) {
+            *self.calls.lock().unwrap().entry(chunk.position).or_insert(0) += 1;
+            chunk.set_block(0, 0, 0, BlockType::Stone);
+        }
+
+        fn seed(&self) -> u32 {
+            0
+        }
+    }
+
+    #[test]
+    fn test_chunks_waiting_for_budget_are_generated_once() {
+        let generator = Arc::new(CountingGenerator::default());
+        let mut streamer = ChunkStreamer::new(
+            StreamingConfig {
+                render_distance: 6,
+                generation_budget: 1,
+                worker_threads: 2,
+                ..Default::default()
+            },
+            generator.clone(),
+        );
+        let mut world = World::new();
+
+        // Edit every chunk as soon as it is inserted; later updates must not
+        // replace it with a freshly generated copy
+        let mut edited = HashSet::new();
+        for _ in 0..10_000 {
+            streamer.update(&mut world, Vec3::ZERO);
+            for position in loaded(&world) {
+                if edited.insert(position) {
+                    world.get_chunk_mut(position).unwrap().set_block(1, 1, 1, BlockType::Planks);
+                }
+            }
+            if streamer.is_idle() && world.dirty_chunks().next().is_none() {
+                break;
+            }
+            streamer.wait_for_jobs();
+        }
+
+        let wanted: ChunkSet = streamer.wanted_chunks((0, 0)).into_iter().collect();
+        assert_eq!(loaded(&world), wanted);
+        let calls = generator.calls.lock().unwrap();
+        assert_eq!(calls.keys().copied().collect::<ChunkSet>(), wanted);
+        assert!(calls.values().all(|&count| count == 1));
+        for chunk in world.chunks() {
+            assert_eq!(chunk.get_block(1, 1, 1), Some(BlockType::Planks));
+        }
+    }
+
+    #[test]
+    fn test_evicted_edits_are_handed_back() {
+        let mut streamer = streamer(1);
+        let mut world = World::new();
+        settle(&mut streamer, &mut world, Vec3::ZERO);
+        assert!(world.chunks().all(|chunk| !chunk.is_modified()));
+        world.set_block(3, 60, 3, BlockType::Planks);
+
+        let far = Vec3::new(200.0, 30.0, 0.0);
+        let mut evicted = Vec::new();
+        for _ in 0..100 {
+            let update = streamer.update(&mut world, far);
+            evicted.extend(update.evicted);
+            if !world.is_loaded((0, 0)) {
+                break;
+            }
+        }
+        // Only the edited chunk needs saving
+        assert_eq!(evicted.len(), 1);
+        assert_eq!(evicted[0].position, (0, 0));
+        assert_eq!(evicted[0].get_block(3, 60, 3), Some(BlockType::Planks));
+    }
+}
@@ -0,0 +1,689 @@
+//! MagicaVoxel `.vox` import and export
+//!
+//! A `.vox` file holds one or more models of at most 256 voxels per side,
+//! each voxel naming an entry in a palette of 255 colours, plus a scene graph
+//! placing the models. On import every colour becomes the block whose
+//! [`BlockType::color`] is nearest; on export every block is written with its
+//! own colour, so exported areas survive the round trip.
+//!
+//! MagicaVoxel is Z-up: the voxel at (x, y, z) becomes the block at
+//! (x, z, -y), which keeps props from being mirrored.
+
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+
+use crate::block::BlockType;
+use crate::chunk::CHUNK_SIZE;
+use crate::world::World;
+
+/// Magic bytes at the start of a `.vox` file
+const MAGIC: &[u8; 4] = b"VOX ";
+/// File version written on export
+const VERSION: u32 = 150;
+/// Largest model side MagicaVoxel accepts
+pub const MAX_MODEL_SIZE: i32 = 256;
+/// Nesting depth after which a scene graph is assumed to contain a cycle
+const MAX_SCENE_DEPTH: usize = 64;
+
+/// Errors from reading a `.vox` file
+#[derive(Debug)]
+pub enum VoxError {
+    Io(io::Error),
+    /// Data that does not follow the `.vox` format
+    Invalid(String),
+}
+
+impl std::fmt::Display for VoxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VoxError::Io(e) => write!(f, "I/O error: {}", e),
+            VoxError::Invalid(reason) => write!(f, "invalid .vox file: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for VoxError {}
+
+impl From<io::Error> for VoxError {
+    fn from(e: io::Error) -> Self {
+        VoxError::Io(e)
+    }
+}
+
+/// A filled voxel of a model
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Voxel {
+    /// Position inside the model, Z up
+    pub position: [u8; 3],
+    /// Palette index; never 0
+    pub color: u8,
+}
+
+/// A box of voxels
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VoxModel {
+    /// Extent along X, Y and Z
+    pub size: [u32; 3],
+    /// Position of the model's lowest corner in the scene, Z up
+    pub offset: [i32; 3],
+    pub voxels: Vec<Voxel>,
+}
+
+/// Contents of a `.vox` file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VoxFile {
+    pub models: Vec<VoxModel>,
+    /// RGBA colour of each palette index; index 0 marks empty voxels
+    pub palette: [[u8; 4]; 256],
+}
+
+impl Default for VoxFile {
+    /// A file with no models and MagicaVoxel's default palette
+    fn default() -> Self {
+        Self { models: Vec::new(), palette: default_palette() }
+    }
+}
+
+impl VoxFile {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, VoxError> {
+        Self::from_bytes(&std::fs::read(path)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), VoxError> {
+        std::fs::write(path, self.to_bytes())?;
+        Ok(())
+    }
+
+    /// Parses a `.vox` file
+    ///
+    /// Reads the SIZE/XYZI model pairs, the RGBA palette and the translations
+    /// in the scene graph; model rotations, materials, layers and cameras are
+    /// ignored. Files without a palette use MagicaVoxel's default one.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, VoxError> {
+        let mut reader = Reader::new(bytes);
+        if reader.take(4)? != MAGIC {
+            return Err(invalid("missing VOX header"));
+        }
+        reader.u32()?;
+        let main = reader.chunk()?;
+        if main.id != b"MAIN" {
+            return Err(invalid("missing MAIN chunk"));
+        }
+
+        let mut file = Self::default();
+        let mut size = None;
+        let mut nodes = HashMap::new();
+        let mut reader = Reader::new(main.children);
+        while !reader.is_at_end() {
+            let chunk = reader.chunk()?;
+            let mut content = Reader::new(chunk.content);
+            match chunk.id {
+                b"SIZE" => size = Some([content.u32()?, content.u32()?, content.u32()?]),
+                b"XYZI" => {
+                    let size = size.take().ok_or_else(|| invalid("XYZI chunk without a SIZE"))?;
+                    let count = content.u32()? as usize;
+                    let mut voxels = Vec::with_capacity(count.min(content.remaining() / 4));
+                    for _ in 0..count {
+                        let voxel = content.take(4)?;
+                        let position = [voxel[0], voxel[1], voxel[2]];
+                        if (0..3).any(|axis| position[axis] as u32 >= size[axis]) {
+                            return Err(invalid("voxel outside its model"));
+                        }
+                        if voxel[3] != 0 {
+                            voxels.push(Voxel { position, color: voxel[3] });
+                        }
+                    }
+                    file.models.push(VoxModel { size, offset: [0; 3], voxels });
+                }
+                b"RGBA" => {
+                    for color in file.palette[1..].iter_mut() {
+                        color.copy_from_slice(content.take(4)?);
+                    }
+                }
+                b"nTRN" => {
+                    let node = content.i32()?;
+                    content.dict()?;
+                    let child = content.i32()?;
+                    // Reserved ID and layer
+                    content.i32()?;
+                    content.i32()?;
+                    let translation = match content.u32()? {
+                        0 => [0; 3],
+                        _ => parse_translation(&content.dict()?)?,
+                    };
+                    nodes.insert(node, Node::Transform { translation, child });
+                }
+                b"nGRP" => {
+                    let node = content.i32()?;
+                    content.dict()?;
+                    let children = (0..content.u32()?)
+                        .map(|_| content.i32())
+                        .collect::<Result<_, _>>()?;
+                    nodes.insert(node, Node::Group { children });
+                }
+                b"nSHP" => {
+                    let node = content.i32()?;
+                    content.dict()?;
+                    let mut models = Vec::new();
+                    for _ in 0..content.u32()? {
+                        models.push(content.i32()?);
+                        content.dict()?;
+                    }
+                    nodes.insert(node, Node::Shape { models });
+                }
+                _ => {}
+            }
+        }
+        if nodes.contains_key(&0) {
+            file.place_models(&nodes, 0, [0; 3], 0)?;
+        }
+        Ok(file)
+    }
+
+    /// Encodes the file, with a scene graph placing every model at its offset
+    ///
+    /// Models larger than [`MAX_MODEL_SIZE`] are written as they are but will
+    /// not open in MagicaVoxel.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut children = Vec::new();
+        for model in &self.models {
+            let mut size = Vec::with_capacity(12);
+            for extent in model.size {
+                size.extend_from_slice(&extent.to_le_bytes());
+            }
+            write_chunk(&mut children, b"SIZE", &size);
+            let mut voxels = Vec::with_capacity(4 + model.voxels.len() * 4);
+            voxels.extend_from_slice(&(model.voxels.len() as u32).to_le_bytes());
+            for voxel in &model.voxels {
+                voxels.extend_from_slice(&voxel.position);
+                voxels.push(voxel.color);
+            }
+            write_chunk(&mut children, b"XYZI", &voxels);
+        }
+
+        // A root transform holding a group with one transform and shape per
+        // model; MagicaVoxel translates a model's centre, not its corner
+        write_chunk(&mut children, b"nTRN", &transform_node(0, 1, -1, None));
+        let mut group = Vec::new();
+        group.extend_from_slice(&1i32.to_le_bytes());
+        write_dict(&mut group, &[]);
+        group.extend_from_slice(&(self.models.len() as u32).to_le_bytes());
+        for index in 0..self.models.len() as i32 {
+            group.extend_from_slice(&(2 + 2 * index).to_le_bytes());
+        }
+        write_chunk(&mut children, b"nGRP", &group);
+        for (index, model) in self.models.iter().enumerate() {
+            let node = 2 + 2 * index as i32;
+            let translation: [i32; 3] =
+                std::array::from_fn(|axis| model.offset[axis] + (model.size[axis] / 2) as i32);
+            let transform = transform_node(node, node + 1, 0, Some(translation));
+            write_chunk(&mut children, b"nTRN", &transform);
+            let mut shape = Vec::new();
+            shape.extend_from_slice(&(node + 1).to_le_bytes());
+            write_dict(&mut shape, &[]);
+            shape.extend_from_slice(&1u32.to_le_bytes());
+            shape.extend_from_slice(&(index as i32).to_le_bytes());
+            write_dict(&mut shape, &[]);
+            write_chunk(&mut children, b"nSHP", &shape);
+        }
+
+        let mut palette = Vec::with_capacity(256 * 4);
+        for color in &self.palette[1..] {
+            palette.extend_from_slice(color);
+        }
+        palette.extend_from_slice(&[0; 4]);
+        write_chunk(&mut children, b"RGBA", &palette);
+
+        let mut bytes = Vec::with_capacity(20 + children.len());
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(b"MAIN");
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        bytes.extend_from_slice(&(children.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&children);
+        bytes
+    }
+
+    /// Every voxel as the block nearest its colour, at Y-up coordinates
+    /// relative to the scene origin
+    ///
+    /// Where models overlap, later models win.
+    pub fn blocks(&self) -> Vec<([i32; 3], BlockType)> {
+        let colors: Vec<BlockType> =
+            self.palette.iter().map(|&color| nearest_block(color)).collect();
+        let mut blocks = Vec::new();
+        for model in &self.models {
+            for voxel in &model.voxels {
+                let [x, y, z]: [i32; 3] =
+                    std::array::from_fn(|axis| model.offset[axis] + voxel.position[axis] as i32);
+                blocks.push(([x, z, -y], colors[voxel.color as usize]));
+            }
+        }
+        blocks
+    }
+
+    /// Places the file's blocks into `world` with the scene origin at
+    /// `origin`, returning the number of blocks set
+    ///
+    /// Chunks are created as needed; blocks outside the world's height range
+    /// are dropped.
+    pub fn import(&self, world: &mut World, origin: [i32; 3]) -> usize {
+        let mut placed = 0;
+        for ([x, y, z], block) in self.blocks() {
+            if world.set_block(origin[0] + x, origin[1] + y, origin[2] + z, block) {
+                placed += 1;
+            }
+        }
+        placed
+    }
+
+    /// Writes the blocks of `world` in `min..max` to a file, split into
+    /// models of at most [`MAX_MODEL_SIZE`] per side
+    ///
+    /// Importing the result at `min` restores the area. Air, unloaded chunks
+    /// and empty models are left out.
+    pub fn export(world: &World, min: [i32; 3], max: [i32; 3]) -> Self {
+        let mut file = Self::default();
+        let mut colors = HashMap::new();
+        for (index, &block) in BlockType::ALL.iter().enumerate().skip(1) {
+            file.palette[index] = block_color(block);
+            colors.insert(block, index as u8);
+        }
+
+        let tiles = |axis: usize| {
+            (min[axis]..max[axis])
+                .step_by(MAX_MODEL_SIZE as usize)
+                .map(move |start| start..(start + MAX_MODEL_SIZE).min(max[axis]))
+        };
+        for xs in tiles(0) {
+            for ys in tiles(1) {
+                for zs in tiles(2) {
+                    let mut voxels = Vec::new();
+                    for x in xs.clone() {
+                        for y in ys.clone() {
+                            for z in zs.clone() {
+                                let Some(&color) =
+                                    world.get_block(x, y, z).and_then(|block| colors.get(&block))
+                                else {
+                                    continue;
+                                };
+                                let position = [x - xs.start, zs.end - 1 - z, y - ys.start];
+                                voxels.push(Voxel { position: position.map(|p| p as u8), color });
+                            }
+                        }
+                    }
+                    if voxels.is_empty() {
+                        continue;
+                    }
+                    // MagicaVoxel's Y runs against our Z
+                    file.models.push(VoxModel {
+                        size: [xs.len(), zs.len(), ys.len()].map(|extent| extent as u32),
+                        offset: [xs.start - min[0], min[2] - zs.end + 1, ys.start - min[1]],
+                        voxels,
+                    });
+                }
+            }
+        }
+        file
+    }
+
+    /// Writes the loaded chunks from `from` to `to`, inclusive, covering the
+    /// layers any of them occupy
+    ///
+    /// Importing the result at the corner given by [`VoxFile::chunk_origin`]
+    /// restores the chunks.
+    pub fn export_chunks(world: &World, from: (i32, i32), to: (i32, i32)) -> Self {
+        match chunk_bounds(world, from, to) {
+            Some((min, max)) => Self::export(world, min, max),
+            None => Self::default(),
+        }
+    }
+
+    /// Lowest block of the area [`VoxFile::export_chunks`] writes for the
+    /// same arguments
+    pub fn chunk_origin(world: &World, from: (i32, i32), to: (i32, i32)) -> [i32; 3] {
+        let size = CHUNK_SIZE as i32;
+        chunk_bounds(world, from, to)
+            .map_or([from.0.min(to.0) * size, 0, from.1.min(to.1) * size], |(min, _)| min)
+    }
+
+    /// Moves every model referenced below `node` to the translation the scene
+    /// graph gives it
+    fn place_models(
+        &mut self,
+        nodes: &HashMap<i32, Node>,
+        node: i32,
+        translation: [i32; 3],
+        depth: usize,
+    ) -> Result<(), VoxError> {
+        if depth > MAX_SCENE_DEPTH {
+            return Err(invalid("scene graph is too deep"));
+        }
+        match nodes.get(&node).ok_or_else(|| invalid("scene graph names a missing node"))? {
+            Node::Transform { translation: offset, child } => {
+                let translation = std::array::from_fn(|axis| translation[axis] + offset[axis]);
+                self.place_models(nodes, *child, translation, depth + 1)?;
+            }
+            Node::Group { children } => {
+                for &child in children {
+                    self.place_models(nodes, child, translation, depth + 1)?;
+                }
+            }
+            Node::Shape { models } => {
+                for &index in models {
+                    let model = usize::try_from(index)
+                        .ok()
+                        .and_then(|index| self.models.get_mut(index))
+                        .ok_or_else(|| invalid("scene graph names a missing model"))?;
+                    model.offset = std::array::from_fn(|axis| {
+                        translation[axis] - (model.size[axis] / 2) as i32
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Blocks covered by the chunks from `from` to `to` over the layers any of
+/// them occupy, or `None` if none hold blocks
+fn chunk_bounds(world: &World, from: (i32, i32), to: (i32, i32)) -> Option<([i32; 3], [i32; 3])> {
+    let (xs, zs) = (from.0.min(to.0)..=from.0.max(to.0), from.1.min(to.1)..=from.1.max(to.1));
+    let (bottom, top) = xs
+        .clone()
+        .flat_map(|x| zs.clone().map(move |z| (x, z)))
+        .filter_map(|position| world.get_chunk(position)?.occupied_range())
+        .reduce(|a, b| (a.0.min(b.0), a.1.max(b.1)))?;
+    let size = CHUNK_SIZE as i32;
+    Some((
+        [xs.start() * size, bottom, zs.start() * size],
+        [(xs.end() + 1) * size, top, (zs.end() + 1) * size],
+    ))
+}
+
+/// The block whose colour is nearest to an RGBA colour, ignoring alpha
+///
+/// Never returns air, so dark voxels stay solid.
+pub fn nearest_block(color: [u8; 4]) -> BlockType {
+    let distance = |block: BlockType| {
+        let target = block.color();
+        (0..3).map(|i| (color[i] as f32 / 255.0 - target[i]).powi(2)).sum::<f32>()
+    };
+    BlockType::ALL
+        .iter()
+        .copied()
+        .filter(|&block| block != BlockType::Air)
+        .min_by(|&a, &b| distance(a).total_cmp(&distance(b)))
+        .expect("there are blocks besides air")
+}
+
+/// A block's colour as opaque RGBA bytes
+fn block_color(block: BlockType) -> [u8; 4] {
+    let [r, g, b] = block.color().map(|channel| (channel * 255.0).round() as u8);
+    [r, g, b, 255]
+}
+
+/// MagicaVoxel's palette for files without an RGBA chunk: a 6x6x6 colour
+/// cube without black, then ramps of red, green, blue and grey
+pub fn default_palette() -> [[u8; 4]; 256] {
+    const STEPS: [u8; 6] = [0xff, 0xcc, 0x99, 0x66, 0x33, 0x00];
+    const RAMP: [u8; 10] = [0xee, 0xdd, 0xbb, 0xaa, 0x88, 0x77, 0x55, 0x44, 0x22, 0x11];
+    let mut colors = Vec::with_capacity(256);
+    colors.push([0; 4]);
+    for r in STEPS {
+        for g in STEPS {
+            for b in STEPS {
+                if (r, g, b) != (0, 0, 0) {
+                    colors.push([r, g, b, 255]);
+                }
+            }
+        }
+    }
+    for channel in 0..3 {
+        for value in RAMP {
+            let mut color = [0, 0, 0, 255];
+            color[channel] = value;
+            colors.push(color);
+        }
+    }
+    for value in RAMP {
+        colors.push([value, value, value, 255]);
+    }
+    colors.try_into().expect("the default palette has 256 entries")
+}
+
+/// Scene graph node
+enum Node {
+    Transform { translation: [i32; 3], child: i32 },
+    Group { children: Vec<i32> },
+    Shape { models: Vec<i32> },
+}
+
+fn invalid(reason: &str) -> VoxError {
+    VoxError::Invalid(reason.to_string())
+}
+
+/// Reads the `_t` translation of a transform frame
+fn parse_translation(frame: &[(String, String)]) -> Result<[i32; 3], VoxError> {
+    let Some((_, value)) = frame.iter().find(|(key, _)| key == "_t") else {
+        return Ok([0; 3]);
+    };
+    let parts: Vec<i32> = value
+        .split_whitespace()
+        .map(str::parse)
+        .collect::<Result<_, _>>()
+        .map_err(|_| invalid("malformed translation"))?;
+    parts.try_into().map_err(|_| invalid("malformed translation"))
+}
+
+fn write_chunk(out: &mut Vec<u8>, id: &[u8; 4], content: &[u8]) {
+    out.extend_from_slice(id);
+    out.extend_from_slice(&(content.len() as u32).to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes());
+    out.extend_from_slice(content);
+}
+
+fn write_dict(out: &mut Vec<u8>, entries: &[(&str, &str)]) {
+    out.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+    for text in entries.iter().flat_map(|&(key, value)| [key, value]) {
+        out.extend_from_slice(&(text.len() as u32).to_le_bytes());
+        out.extend_from_slice(text.as_bytes());
+    }
+}
+
+/// Content of a transform node with a single frame
+fn transform_node(node: i32, child: i32, layer: i32, translation: Option<[i32; 3]>) -> Vec<u8> {
+    let mut content = Vec::new();
+    content.extend_from_slice(&node.to_le_bytes());
+    write_dict(&mut content, &[]);
+    content.extend_from_slice(&child.to_le_bytes());
+    content.extend_from_slice(&(-1i32).to_le_bytes());
+    content.extend_from_slice(&layer.to_le_bytes());
+    content.extend_from_slice(&1u32.to_le_bytes());
+    match translation {
+        Some([x, y, z]) => write_dict(&mut content, &[("_t", &format!("{} {} {}", x, y, z))]),
+        None => write_dict(&mut content, &[]),
+    }
+    content
+}
+
+/// A chunk of a `.vox` file, before parsing
+struct RawChunk<'a> {
+    id: &'a [u8],
+    content: &'a [u8],
+    /// Chunks nested inside this one
+    children: &'a [u8],
+}
+
+/// Cursor over little-endian `.vox` data
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+
+    fn is_at_end(&self) -> bool {
+        self.position == self.bytes.len()
+    }
+
+    fn remaining(&self) -> usize {
+        self.bytes.len() - self.position
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], VoxError> {
+        if len > self.remaining() {
+            return Err(invalid("unexpected end of data"));
+        }
+        let bytes = &self.bytes[self.position..self.position + len];
+        self.position += len;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, VoxError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn i32(&mut self) -> Result<i32, VoxError> {
+        Ok(i32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn chunk(&mut self) -> Result<RawChunk<'a>, VoxError> {
+        let id = self.take(4)?;
+        let content = self.u32()? as usize;
+        let children = self.u32()? as usize;
+        Ok(RawChunk { id, content: self.take(content)?, children: self.take(children)? })
+    }
+
+    fn string(&mut self) -> Result<String, VoxError> {
+        let len = self.u32()? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|_| invalid("string is not UTF-8"))
+    }
+
+    fn dict(&mut self) -> Result<Vec<(String, String)>, VoxError> {
+        (0..self.u32()?).map(|_| Ok((self.string()?, self.string()?))).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A world with every block type, spread over four chunks and below y = 0
+    fn sample_world() -> World {
+        let mut world = World::new();
+        for (i, &block) in BlockType::ALL.iter().enumerate().skip(1) {
+            let i = i as i32;
+            world.set_block(i - 9, i * 3 - 20, 5 - i, block);
+            world.set_block(-1, i, 0, block);
+        }
+        world
+    }
+
+    fn assert_same_blocks(a: &World, b: &World, min: [i32; 3], max: [i32; 3]) {
+        for x in min[0]..max[0] {
+            for y in min[1]..max[1] {
+                for z in min[2]..max[2] {
+                    let block = |world: &World| world.get_block(x, y, z).unwrap_or(BlockType::Air);
+                    assert_eq!(block(a), block(b), "block at {:?}", (x, y, z));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_world_round_trip() {
+        let world = sample_world();
+        let (min, max) = ([-10, -20, -14], [10, 40, 6]);
+        let file = VoxFile::export(&world, min, max);
+        assert_eq!(file.models.len(), 1);
+        assert_eq!(file.models[0].size, [20, 20, 60]);
+
+        let loaded = VoxFile::from_bytes(&file.to_bytes()).unwrap();
+        assert_eq!(loaded, file);
+        let mut imported = World::new();
+        assert_eq!(loaded.import(&mut imported, min), file.models[0].voxels.len());
+        assert_same_blocks(&world, &imported, min, max);
+    }
+
+    #[test]
+    fn test_large_areas_split_into_models() {
+        let mut world = World::new();
+        world.set_block(0, -64, 0, BlockType::Bedrock);
+        world.set_block(300, 250, -2, BlockType::GoldOre);
+        world.set_block(256, 191, 1, BlockType::Log);
+        let (min, max) = ([0, -64, -2], [301, 251, 2]);
+        let file = VoxFile::export(&world, min, max);
+        assert_eq!(file.models.len(), 3);
+        assert!(file.models.iter().all(|model| model.size.iter().all(|&s| s <= 256)));
+
+        let loaded = VoxFile::from_bytes(&file.to_bytes()).unwrap();
+        assert_eq!(loaded, file);
+        let mut imported = World::new();
+        assert_eq!(loaded.import(&mut imported, min), 3);
+        for (x, y, z) in [(0, -64, 0), (300, 250, -2), (256, 191, 1)] {
+            assert_eq!(imported.get_block(x, y, z), world.get_block(x, y, z));
+        }
+    }
+
+    #[test]
+    fn test_chunk_export_round_trip() {
+        let world = sample_world();
+        let file = VoxFile::export_chunks(&world, (0, 0), (-1, -1));
+        let origin = VoxFile::chunk_origin(&world, (0, 0), (-1, -1));
+        assert_eq!(origin, [-16, -32, -16]);
+
+        let mut imported = World::new();
+        VoxFile::from_bytes(&file.to_bytes()).unwrap().import(&mut imported, origin);
+        assert_same_blocks(&world, &imported, [-16, -32, -16], [16, 32, 16]);
+        assert!(VoxFile::export_chunks(&world, (5, 5), (6, 6)).models.is_empty());
+    }
+
+    #[test]
+    fn test_colors_map_to_nearest_block() {
+        for &block in &BlockType::ALL[1..] {
+            assert_eq!(nearest_block(block_color(block)), block);
+        }
+        assert_eq!(nearest_block([0, 0, 0, 255]), BlockType::Bedrock);
+        assert_eq!(nearest_block([250, 250, 255, 255]), BlockType::Snow);
+        assert_eq!(nearest_block([30, 110, 220, 0]), BlockType::Water);
+    }
+
+    #[test]
+    fn test_reads_minimal_files() {
+        // Two models, no scene graph and no palette, as older exporters write
+        let mut children = Vec::new();
+        for (size, voxel) in [([2u32, 1, 1], [1u8, 0, 0, 1]), ([1, 1, 3], [0, 0, 2, 255])] {
+            let size: Vec<u8> = size.iter().flat_map(|extent| extent.to_le_bytes()).collect();
+            write_chunk(&mut children, b"SIZE", &size);
+            let mut voxels = 1u32.to_le_bytes().to_vec();
+            voxels.extend_from_slice(&voxel);
+            write_chunk(&mut children, b"XYZI", &voxels);
+        }
+        write_chunk(&mut children, b"MATL", &[0; 8]);
+        let mut bytes = b"VOX ".to_vec();
+        bytes.extend_from_slice(&200u32.to_le_bytes());
+        bytes.extend_from_slice(b"MAIN");
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        bytes.extend_from_slice(&(children.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&children);
+
+        let file = VoxFile::from_bytes(&bytes).unwrap();
+        assert_eq!(file.models.len(), 2);
+        assert_eq!(file.palette, default_palette());
+        assert_eq!(file.palette[1], [255, 255, 255, 255]);
+        assert_eq!(file.palette[255], [0x11, 0x11, 0x11, 255]);
+        assert_eq!(
+            file.blocks(),
+            vec![([1, 0, 0], BlockType::Snow), ([0, 2, 0], BlockType::Bedrock)]
+        );
+
+        let truncated = &bytes[..bytes.len() - 3];
+        assert!(matches!(VoxFile::from_bytes(truncated), Err(VoxError::Invalid(_))));
+        assert!(matches!(VoxFile::from_bytes(b"PNG nope"), Err(VoxError::Invalid(_))));
+    }
+}
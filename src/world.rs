@@ -0,0 +1,303 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::block::BlockType;
+use crate::chunk::{Chunk, CHUNK_SIZE, MAX_Y, MIN_Y};
+use crate::mesh::ChunkNeighbors;
+
+/// Offsets to the four chunks sharing a border with a chunk
+const NEIGHBOR_OFFSETS: [(i32, i32); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+
+/// Collection of loaded chunks addressed by chunk coordinates
+pub struct World {
+    chunks: HashMap<(i32, i32), Chunk>,
+    /// Chunks whose mesh is out of date
+    dirty: HashSet<(i32, i32)>,
+}
+
+impl Default for World {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl World {
+    /// Creates a new world with no chunks loaded
+    pub fn new() -> Self {
+        Self {
+            chunks: HashMap::new(),
+            dirty: HashSet::new(),
+        }
+    }
+
+    /// Converts world block coordinates on the X/Z plane to the chunk that
+    /// contains them and the local coordinates inside that chunk
+    pub fn world_to_chunk(x: i32, z: i32) -> ((i32, i32), (usize, usize)) {
+        let size = CHUNK_SIZE as i32;
+        let chunk = (x.div_euclid(size), z.div_euclid(size));
+        let local = (x.rem_euclid(size) as usize, z.rem_euclid(size) as usize);
+        (chunk, local)
+    }
+
+    /// Converts chunk coordinates plus local coordinates back to world coordinates
+    pub fn chunk_to_world(chunk: (i32, i32), local_x: usize, local_z: usize) -> (i32, i32) {
+        (
+            chunk.0 * CHUNK_SIZE as i32 + local_x as i32,
+            chunk.1 * CHUNK_SIZE as i32 + local_z as i32,
+        )
+    }
+
+    /// Gets the block at the specified world coordinates
+    ///
+    /// Returns `None` if the chunk is not loaded or `y` is outside
+    /// `MIN_Y..MAX_Y`.
+    pub fn get_block(&self, x: i32, y: i32, z: i32) -> Option<BlockType> {
+        if !(MIN_Y..MAX_Y).contains(&y) {
+            return None;
+        }
+        let (chunk, (local_x, local_z)) = Self::world_to_chunk(x, z);
+        self.chunks.get(&chunk)?.get_block_at(local_x, y, local_z)
+    }
+
+    /// Sets the block at the specified world coordinates, creating the
+    /// containing chunk if it is not loaded yet
+    ///
+    /// Marks the chunk dirty, along with any loaded neighbour whose border
+    /// faces depend on the changed block.
+    pub fn set_block(&mut self, x: i32, y: i32, z: i32, block: BlockType) -> bool {
+        if !(MIN_Y..MAX_Y).contains(&y) {
+            return false;
+        }
+        let (chunk, (local_x, local_z)) = Self::world_to_chunk(x, z);
+        if !self
+            .get_or_create_chunk(chunk)
+            .set_block_at(local_x, y, local_z, block)
+        {
+            return false;
+        }
+
+        self.dirty.insert(chunk);
+        let last = CHUNK_SIZE - 1;
+        let touching = [
+            ((1, 0), local_x == last),
+            ((-1, 0), local_x == 0),
+            ((0, 1), local_z == last),
+            ((0, -1), local_z == 0),
+        ];
+        for ((dx, dz), on_border) in touching {
+            let neighbor = (chunk.0 + dx, chunk.1 + dz);
+            if on_border && self.chunks.contains_key(&neighbor) {
+                self.dirty.insert(neighbor);
+            }
+        }
+        true
+    }
+
+    /// Gets the chunk at the specified chunk coordinates
+    pub fn get_chunk(&self, position: (i32, i32)) -> Option<&Chunk> {
+        self.chunks.get(&position)
+    }
+
+    /// Gets the chunk at the specified chunk coordinates mutably
+    ///
+    /// Edits made through this reference are not tracked; call
+    /// [`World::mark_dirty`] afterwards if the chunk needs remeshing.
+    pub fn get_chunk_mut(&mut self, position: (i32, i32)) -> Option<&mut Chunk> {
+        self.chunks.get_mut(&position)
+    }
+
+    /// Gets the chunk at the specified chunk coordinates, creating an empty one if needed
+    pub fn get_or_create_chunk(&mut self, position: (i32, i32)) -> &mut Chunk {
+        if !self.chunks.contains_key(&position) {
+            self.mark_loaded(position);
+        }
+        self.chunks
+            .entry(position)
+            .or_insert_with(|| Chunk::new(position))
+    }
+
+    /// Inserts a chunk at its own position, returning the chunk it replaced
+    pub fn insert_chunk(&mut self, chunk: Chunk) -> Option<Chunk> {
+        self.mark_loaded(chunk.position);
+        self.chunks.insert(chunk.position, chunk)
+    }
+
+    /// Removes the chunk at the specified chunk coordinates
+    pub fn remove_chunk(&mut self, position: (i32, i32)) -> Option<Chunk> {
+        let chunk = self.chunks.remove(&position)?;
+        self.dirty.remove(&position);
+        self.mark_neighbors_dirty(position);
+        Some(chunk)
+    }
+
+    /// Gets the loaded chunks bordering the chunk at `position`
+    pub fn neighbors(&self, position: (i32, i32)) -> ChunkNeighbors<'_> {
+        let (x, z) = position;
+        ChunkNeighbors {
+            east: self.chunks.get(&(x + 1, z)),
+            west: self.chunks.get(&(x - 1, z)),
+            south: self.chunks.get(&(x, z + 1)),
+            north: self.chunks.get(&(x, z - 1)),
+        }
+    }
+
+    /// Flags a loaded chunk as needing a new mesh
+    pub fn mark_dirty(&mut self, position: (i32, i32)) {
+        if self.chunks.contains_key(&position) {
+            self.dirty.insert(position);
+        }
+    }
+
+    /// Returns true if the chunk at `position` needs a new mesh
+    pub fn is_dirty(&self, position: (i32, i32)) -> bool {
+        self.dirty.contains(&position)
+    }
+
+    /// Iterates over the coordinates of chunks that need a new mesh
+    pub fn dirty_chunks(&self) -> impl Iterator<Item = (i32, i32)> + '_ {
+        self.dirty.iter().copied()
+    }
+
+    /// Clears the dirty flag once the chunk has been remeshed
+    pub fn clear_dirty(&mut self, position: (i32, i32)) {
+        self.dirty.remove(&position);
+    }
+
+    /// Removes and returns every dirty chunk coordinate
+    pub fn take_dirty(&mut self) -> Vec<(i32, i32)> {
+        self.dirty.drain().collect()
+    }
+
+    /// A new chunk needs meshing, and its neighbours can now cull their
+    /// border faces against it
+    fn mark_loaded(&mut self, position: (i32, i32)) {
+        self.dirty.insert(position);
+        self.mark_neighbors_dirty(position);
+    }
+
+    fn mark_neighbors_dirty(&mut self, position: (i32, i32)) {
+        for (dx, dz) in NEIGHBOR_OFFSETS {
+            self.mark_dirty((position.0 + dx, position.1 + dz));
+        }
+    }
+
+    /// Returns true if a chunk is loaded at the specified chunk coordinates
+    pub fn is_loaded(&self, position: (i32, i32)) -> bool {
+        self.chunks.contains_key(&position)
+    }
+
+    /// Iterates over all loaded chunks
+    pub fn chunks(&self) -> impl Iterator<Item = &Chunk> {
+        self.chunks.values()
+    }
+
+    /// Iterates mutably over all loaded chunks
+    ///
+    /// Like [`World::get_chunk_mut`], edits made this way are not tracked.
+    pub fn chunks_mut(&mut self) -> impl Iterator<Item = &mut Chunk> {
+        self.chunks.values_mut()
+    }
+
+    /// Iterates over the coordinates of all loaded chunks
+    pub fn chunk_positions(&self) -> impl Iterator<Item = (i32, i32)> + '_ {
+        self.chunks.keys().copied()
+    }
+
+    /// Number of loaded chunks
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_world_to_chunk_negative_coordinates() {
+        assert_eq!(World::world_to_chunk(0, 0), ((0, 0), (0, 0)));
+        assert_eq!(World::world_to_chunk(15, 16), ((0, 1), (15, 0)));
+        assert_eq!(World::world_to_chunk(-1, -16), ((-1, -1), (15, 0)));
+        assert_eq!(World::world_to_chunk(-17, -33), ((-2, -3), (15, 15)));
+    }
+
+    #[test]
+    fn test_world_chunk_round_trip() {
+        for x in -40..40 {
+            for z in [-33, -1, 0, 17] {
+                let (chunk, (lx, lz)) = World::world_to_chunk(x, z);
+                assert_eq!(World::chunk_to_world(chunk, lx, lz), (x, z));
+            }
+        }
+    }
+
+    #[test]
+    fn test_set_block_creates_chunk() {
+        let mut world = World::new();
+        assert_eq!(world.get_block(-5, 10, 3), None);
+        assert!(world.set_block(-5, 10, 3, BlockType::Stone));
+        assert!(world.is_loaded((-1, 0)));
+        assert_eq!(world.get_block(-5, 10, 3), Some(BlockType::Stone));
+        assert_eq!(
+            world.get_chunk((-1, 0)).unwrap().get_block(11, 10, 3),
+            Some(BlockType::Stone)
+        );
+        assert_eq!(world.get_block(-5, 11, 3), Some(BlockType::Air));
+        assert_eq!(world.chunk_count(), 1);
+    }
+
+    #[test]
+    fn test_border_edit_marks_neighbor_dirty() {
+        let mut world = World::new();
+        world.insert_chunk(Chunk::new((0, 0)));
+        world.insert_chunk(Chunk::new((-1, 0)));
+        world.insert_chunk(Chunk::new((0, 1)));
+        assert_eq!(world.take_dirty().len(), 3);
+
+        // Interior edit only touches its own chunk
+        world.set_block(5, 10, 5, BlockType::Stone);
+        assert_eq!(world.take_dirty(), vec![(0, 0)]);
+
+        // x == 0 borders the chunk to the west
+        world.set_block(0, 10, 5, BlockType::Stone);
+        let mut dirty = world.take_dirty();
+        dirty.sort();
+        assert_eq!(dirty, vec![(-1, 0), (0, 0)]);
+
+        // Corner block touches both loaded neighbours
+        world.set_block(0, 10, 15, BlockType::Stone);
+        let mut dirty = world.take_dirty();
+        dirty.sort();
+        assert_eq!(dirty, vec![(-1, 0), (0, 0), (0, 1)]);
+    }
+
+    #[test]
+    fn test_loading_and_unloading_marks_neighbors() {
+        let mut world = World::new();
+        world.insert_chunk(Chunk::new((0, 0)));
+        world.take_dirty();
+
+        world.insert_chunk(Chunk::new((1, 0)));
+        assert!(world.is_dirty((0, 0)));
+        assert!(world.is_dirty((1, 0)));
+        assert!(world.neighbors((0, 0)).east.is_some());
+        assert!(world.neighbors((0, 0)).west.is_none());
+        world.take_dirty();
+
+        world.remove_chunk((1, 0));
+        assert_eq!(world.dirty_chunks().collect::<Vec<_>>(), vec![(0, 0)]);
+    }
+
+    #[test]
+    fn test_out_of_height_range() {
+        let mut world = World::new();
+        assert!(!world.set_block(0, MIN_Y - 1, 0, BlockType::Stone));
+        assert!(!world.set_block(0, MAX_Y, 0, BlockType::Stone));
+        assert_eq!(world.chunk_count(), 0);
+
+        // Negative layers down to MIN_Y are part of the world
+        assert!(world.set_block(0, -1, 0, BlockType::Stone));
+        assert!(world.set_block(0, MIN_Y, 0, BlockType::Bedrock));
+        assert_eq!(world.get_block(0, -1, 0), Some(BlockType::Stone));
+        assert_eq!(world.get_block(0, MIN_Y, 0), Some(BlockType::Bedrock));
+    }
+}
@@ -0,0 +1,326 @@
+use crate::block::BlockType;
+use crate::chunk::CHUNK_SIZE;
+use crate::world::World;
+
+use super::{FractalNoise, GeneratorSettings};
+
+/// Spacing in blocks between biome samples used for blending
+const BLEND_CELL: i32 = 4;
+/// Radius in blocks over which terrain parameters are blended
+const BLEND_RADIUS: i32 = 12;
+
+/// Climate zones with their own surface blocks and terrain shape
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Biome {
+    Ocean,
+    Plains,
+    Desert,
+    Forest,
+    Tundra,
+    Mountains,
+}
+
+/// Terrain shape parameters that are blended across biome borders
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct BiomeParams {
+    /// Offset added to the base height
+    pub height_offset: f64,
+    /// Multiplier for the hill amplitude
+    pub hilliness: f64,
+    /// Multiplier for the mountain height
+    pub mountains: f64,
+}
+
+/// Noise values deciding the biome of a column, each roughly in [-1, 1]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Climate {
+    pub temperature: f64,
+    pub humidity: f64,
+    /// Low values are open ocean, high values are inland highlands
+    pub continentalness: f64,
+}
+
+impl Biome {
+    pub const ALL: [Biome; 6] = [
+        Biome::Ocean,
+        Biome::Plains,
+        Biome::Desert,
+        Biome::Forest,
+        Biome::Tundra,
+        Biome::Mountains,
+    ];
+
+    /// Picks the biome for a climate sample
+    pub fn from_climate(climate: Climate) -> Self {
+        if climate.continentalness < -0.25 {
+            Biome::Ocean
+        } else if climate.continentalness > 0.3 {
+            Biome::Mountains
+        } else if climate.temperature < -0.25 {
+            Biome::Tundra
+        } else if climate.temperature > 0.2 && climate.humidity < 0.0 {
+            Biome::Desert
+        } else if climate.humidity > 0.1 {
+            Biome::Forest
+        } else {
+            Biome::Plains
+        }
+    }
+
+    pub fn params(&self) -> BiomeParams {
+        let (height_offset, hilliness, mountains) = match self {
+            Biome::Ocean => (-12.0, 0.6, 0.0),
+            Biome::Plains => (0.0, 0.5, 0.1),
+            Biome::Desert => (1.0, 0.7, 0.15),
+            Biome::Forest => (2.0, 1.0, 0.3),
+            Biome::Tundra => (1.0, 0.8, 0.4),
+            Biome::Mountains => (6.0, 1.2, 1.0),
+        };
+        BiomeParams { height_offset, hilliness, mountains }
+    }
+
+    /// Block placed on top of each column
+    pub fn surface_block(&self) -> BlockType {
+        match self {
+            Biome::Ocean | Biome::Desert => BlockType::Sand,
+            Biome::Plains | Biome::Forest => BlockType::Grass,
+            Biome::Tundra => BlockType::Snow,
+            Biome::Mountains => BlockType::Stone,
+        }
+    }
+
+    /// Block filling the layer between the surface and the stone below
+    pub fn subsurface_block(&self) -> BlockType {
+        match self {
+            Biome::Ocean | Biome::Desert => BlockType::Sand,
+            Biome::Plains | Biome::Forest | Biome::Tundra => BlockType::Dirt,
+            Biome::Mountains => BlockType::Stone,
+        }
+    }
+
+    /// Chance that each tree attempt in a chunk grows a tree
+    pub fn tree_chance(&self) -> f64 {
+        match self {
+            Biome::Forest => 0.6,
+            Biome::Tundra => 0.15,
+            Biome::Plains => 0.04,
+            Biome::Mountains => 0.03,
+            Biome::Ocean | Biome::Desert => 0.0,
+        }
+    }
+
+    /// Chances of a grass surface growing tall grass and flowers
+    pub fn plant_chances(&self) -> (f64, f64) {
+        match self {
+            Biome::Plains => (0.2, 0.03),
+            Biome::Forest => (0.1, 0.01),
+            Biome::Mountains => (0.05, 0.005),
+            Biome::Ocean | Biome::Desert | Biome::Tundra => (0.0, 0.0),
+        }
+    }
+
+    /// Colour used when drawing biome maps
+    pub fn map_color(&self) -> [u8; 3] {
+        match self {
+            Biome::Ocean => [40, 70, 170],
+            Biome::Plains => [130, 200, 90],
+            Biome::Desert => [230, 210, 130],
+            Biome::Forest => [30, 120, 40],
+            Biome::Tundra => [225, 235, 245],
+            Biome::Mountains => [120, 110, 105],
+        }
+    }
+}
+
+impl BiomeParams {
+    fn add_weighted(&mut self, other: BiomeParams, weight: f64) {
+        self.height_offset += other.height_offset * weight;
+        self.hilliness += other.hilliness * weight;
+        self.mountains += other.mountains * weight;
+    }
+
+    fn scaled(self, factor: f64) -> Self {
+        Self {
+            height_offset: self.height_offset * factor,
+            hilliness: self.hilliness * factor,
+            mountains: self.mountains * factor,
+        }
+    }
+}
+
+/// Samples the climate noise fields that decide biomes
+pub struct BiomeSource {
+    temperature: FractalNoise,
+    humidity: FractalNoise,
+    continentalness: FractalNoise,
+}
+
+impl BiomeSource {
+    pub fn new(settings: &GeneratorSettings) -> Self {
+        let seed = settings.seed;
+        Self {
+            temperature: FractalNoise::new(seed.wrapping_add(10), 3, settings.biome_frequency),
+            humidity: FractalNoise::new(seed.wrapping_add(11), 3, settings.biome_frequency),
+            continentalness: FractalNoise::new(seed.wrapping_add(12), 4, settings.continent_frequency),
+        }
+    }
+
+    pub fn climate(&self, x: i32, z: i32) -> Climate {
+        let (x, z) = (x as f64, z as f64);
+        Climate {
+            temperature: self.temperature.fbm(x, z),
+            humidity: self.humidity.fbm(x, z),
+            continentalness: self.continentalness.fbm(x, z),
+        }
+    }
+
+    /// Biome of the column at world coordinates (x, z)
+    pub fn biome_at(&self, x: i32, z: i32) -> Biome {
+        Biome::from_climate(self.climate(x, z))
+    }
+
+    /// Terrain parameters at (x, z), blended over the surrounding biomes
+    pub fn blended_params(&self, x: i32, z: i32) -> BiomeParams {
+        blend(x, z, |cell_x, cell_z| self.biome_at(cell_x * BLEND_CELL, cell_z * BLEND_CELL))
+    }
+}
+
+/// Weighted average of the biome parameters on the blend grid around (x, z)
+///
+/// The weight falls smoothly to zero at `BLEND_RADIUS`, so the result is
+/// continuous and neighbouring columns never jump in height at a border.
+fn blend(x: i32, z: i32, biome_at_cell: impl Fn(i32, i32) -> Biome) -> BiomeParams {
+    let cells = BLEND_RADIUS / BLEND_CELL;
+    let (center_x, center_z) = (x.div_euclid(BLEND_CELL), z.div_euclid(BLEND_CELL));
+    let radius_squared = (BLEND_RADIUS * BLEND_RADIUS) as f64;
+
+    let mut total = BiomeParams::default();
+    let mut total_weight = 0.0;
+    for cell_x in center_x - cells..=center_x + cells + 1 {
+        for cell_z in center_z - cells..=center_z + cells + 1 {
+            let dx = (cell_x * BLEND_CELL - x) as f64;
+            let dz = (cell_z * BLEND_CELL - z) as f64;
+            let falloff = 1.0 - (dx * dx + dz * dz) / radius_squared;
+            if falloff <= 0.0 {
+                continue;
+            }
+            let weight = falloff * falloff;
+            total.add_weighted(biome_at_cell(cell_x, cell_z).params(), weight);
+            total_weight += weight;
+        }
+    }
+    total.scaled(1.0 / total_weight)
+}
+
+/// Biomes and blended parameters for every column of one chunk
+///
+/// Samples the blend grid once per chunk instead of once per column. The
+/// grid also covers the columns bordering the chunk, so later stages can
+/// look one block past its edges.
+pub struct ChunkBiomes {
+    biomes: [[Biome; CHUNK_SIZE]; CHUNK_SIZE],
+    params: [[BiomeParams; CHUNK_SIZE]; CHUNK_SIZE],
+    grid: Vec<Biome>,
+    first_cell: (i32, i32),
+    cells: usize,
+}
+
+impl ChunkBiomes {
+    pub fn new(source: &BiomeSource, position: (i32, i32)) -> Self {
+        let (min_x, min_z) = World::chunk_to_world(position, 0, 0);
+        let margin = BLEND_RADIUS / BLEND_CELL + 1;
+        let first_cell = (
+            min_x.div_euclid(BLEND_CELL) - margin,
+            min_z.div_euclid(BLEND_CELL) - margin,
+        );
+        let cells = (CHUNK_SIZE as i32 / BLEND_CELL + 2 * margin + 1) as usize;
+
+        let mut grid = vec![Biome::Plains; cells * cells];
+        for i in 0..cells {
+            for j in 0..cells {
+                let (cell_x, cell_z) = (first_cell.0 + i as i32, first_cell.1 + j as i32);
+                grid[i * cells + j] = source.biome_at(cell_x * BLEND_CELL, cell_z * BLEND_CELL);
+            }
+        }
+
+        let mut chunk_biomes = Self {
+            biomes: [[Biome::Plains; CHUNK_SIZE]; CHUNK_SIZE],
+            params: [[BiomeParams::default(); CHUNK_SIZE]; CHUNK_SIZE],
+            grid,
+            first_cell,
+            cells,
+        };
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                let (world_x, world_z) = World::chunk_to_world(position, x, z);
+                chunk_biomes.biomes[x][z] = source.biome_at(world_x, world_z);
+                chunk_biomes.params[x][z] = chunk_biomes.params_at(world_x, world_z);
+            }
+        }
+        chunk_biomes
+    }
+
+    /// Biome of the column at local coordinates
+    pub fn biome(&self, x: usize, z: usize) -> Biome {
+        self.biomes[x][z]
+    }
+
+    /// Blended terrain parameters of the column at local coordinates
+    pub fn params(&self, x: usize, z: usize) -> BiomeParams {
+        self.params[x][z]
+    }
+
+    /// Blended terrain parameters of a column in or directly next to the chunk
+    pub fn params_at(&self, world_x: i32, world_z: i32) -> BiomeParams {
+        blend(world_x, world_z, |cell_x, cell_z| {
+            let i = (cell_x - self.first_cell.0) as usize;
+            let j = (cell_z - self.first_cell.1) as usize;
+            self.grid[i * self.cells + j]
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunk_biomes_match_direct_sampling() {
+        let source = BiomeSource::new(&GeneratorSettings::default());
+        let position = (-3, 5);
+        let biomes = ChunkBiomes::new(&source, position);
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                let (world_x, world_z) = World::chunk_to_world(position, x, z);
+                assert_eq!(biomes.biome(x, z), source.biome_at(world_x, world_z));
+                assert_eq!(biomes.params(x, z), source.blended_params(world_x, world_z));
+            }
+        }
+        let (min_x, min_z) = World::chunk_to_world(position, 0, 0);
+        for (x, z) in [(min_x - 1, min_z - 1), (min_x + 16, min_z + 16), (min_x - 1, min_z + 7)] {
+            assert_eq!(biomes.params_at(x, z), source.blended_params(x, z));
+        }
+    }
+
+    #[test]
+    fn test_blending_is_smooth() {
+        let source = BiomeSource::new(&GeneratorSettings::default());
+        for x in -1000..1000 {
+            let a = source.blended_params(x, 300);
+            let b = source.blended_params(x + 1, 300);
+            assert!((a.height_offset - b.height_offset).abs() < 2.0, "jump at x = {}", x);
+        }
+    }
+
+    #[test]
+    fn test_every_biome_appears() {
+        let source = BiomeSource::new(&GeneratorSettings::default());
+        let mut seen = std::collections::HashSet::new();
+        for x in (-8000..8000).step_by(128) {
+            for z in (-8000..8000).step_by(128) {
+                seen.insert(source.biome_at(x, z));
+            }
+        }
+        assert_eq!(seen.len(), Biome::ALL.len(), "found only {:?}", seen);
+    }
+}
@@ -0,0 +1,315 @@
+use std::f64::consts::PI;
+
+use noise::{NoiseFn, Perlin};
+
+use crate::block::BlockType;
+use crate::chunk::{Chunk, CHUNK_SIZE, MAX_Y, MIN_Y};
+use crate::world::World;
+
+use super::random::Rng;
+use super::{GeneratorSettings, Heightmap, BEDROCK_LAYERS};
+
+/// Lowest layer caves may carve, one layer above the bedrock band so cave
+/// floors never expose the void
+const CAVE_FLOOR: i32 = MIN_Y + BEDROCK_LAYERS as i32 + 1;
+/// Worms may start this many chunks away from the chunk they carve into
+const WORM_REACH: i32 = 5;
+/// Worms stop once they are this far from their start horizontally, so they
+/// never leave the chunks that look for them
+const WORM_RANGE: f64 = ((WORM_REACH - 1) * CHUNK_SIZE as i32) as f64;
+/// Salt separating the worm random stream from other stages
+const WORM_SALT: u64 = 0x776f_726d;
+/// Cheese caves stay this far below the surface, leaving entrances to tunnels
+const CHEESE_COVER: i32 = 8;
+
+/// Carves caves out of the solid terrain of a chunk
+///
+/// Three kinds of caves are combined:
+/// - cheese caves: large caverns where a 3D noise field is high
+/// - spaghetti caves: long tunnels where two 3D noise fields are both near zero
+/// - worms: winding tunnels walked by carvers that start in a chunk and cross
+///   into its neighbours
+///
+/// Worms are replayed from every chunk within reach, so the result depends
+/// only on the chunk position and chunks still generate in any order.
+pub struct CaveCarver {
+    seed: u32,
+    cheese: Perlin,
+    tunnel_a: Perlin,
+    tunnel_b: Perlin,
+    cave_frequency: f64,
+    cave_threshold: f64,
+    tunnel_frequency: f64,
+    tunnel_width: f64,
+    worm_chance: f64,
+    worm_length: u32,
+    ocean_cover: i32,
+}
+
+impl CaveCarver {
+    pub fn new(settings: &GeneratorSettings) -> Self {
+        let seed = settings.seed;
+        Self {
+            seed,
+            cheese: Perlin::new(seed.wrapping_add(20)),
+            tunnel_a: Perlin::new(seed.wrapping_add(21)),
+            tunnel_b: Perlin::new(seed.wrapping_add(22)),
+            cave_frequency: settings.cave_frequency,
+            cave_threshold: settings.cave_threshold,
+            tunnel_frequency: settings.tunnel_frequency,
+            tunnel_width: settings.tunnel_width,
+            worm_chance: settings.worm_chance,
+            worm_length: settings.worm_length,
+            ocean_cover: settings.cave_ocean_cover as i32,
+        }
+    }
+
+    /// Carves every kind of cave into a chunk whose columns are already filled
+    pub fn carve(&self, chunk: &mut Chunk, heights: &Heightmap) {
+        let limits = self.carve_limits(heights);
+
+        for (x, column) in limits.iter().enumerate() {
+            for (z, &limit) in column.iter().enumerate() {
+                let Some(limit) = limit else { continue };
+                let (world_x, world_z) = World::chunk_to_world(chunk.position, x, z);
+                let height = heights.get(x as i32, z as i32) as i32;
+                for y in CAVE_FLOOR..=limit {
+                    let cheese = y + CHEESE_COVER <= height && self.is_cheese(world_x, y, world_z);
+                    if cheese || self.is_tunnel(world_x, y, world_z) {
+                        carve_block(chunk, x, y, z);
+                    }
+                }
+            }
+        }
+
+        self.carve_worms(chunk, &limits);
+    }
+
+    /// Highest layer that may be carved in each column, or `None` if the
+    /// column is too shallow for caves
+    ///
+    /// Next to water the limit keeps a solid cover under the sea, river or
+    /// lake bed, so caves never open into water from below or from the side.
+    fn carve_limits(&self, heights: &Heightmap) -> [[Option<i32>; CHUNK_SIZE]; CHUNK_SIZE] {
+        let mut limits = [[None; CHUNK_SIZE]; CHUNK_SIZE];
+        for (x, column) in limits.iter_mut().enumerate() {
+            for (z, limit) in column.iter_mut().enumerate() {
+                let (x, z) = (x as i32, z as i32);
+                let neighbours = (-1..=1).flat_map(|dx| (-1..=1).map(move |dz| (x + dx, z + dz)));
+                let lowest = neighbours.clone().map(|(x, z)| heights.get(x, z)).min().unwrap();
+                let wet = neighbours.clone().any(|(x, z)| heights.water(x, z).is_some());
+                let top = if wet {
+                    lowest as i32 - self.ocean_cover - 1
+                } else {
+                    heights.get(x, z) as i32
+                };
+                *limit = Some(top).filter(|&top| top >= CAVE_FLOOR);
+            }
+        }
+        limits
+    }
+
+    fn is_cheese(&self, x: i32, y: i32, z: i32) -> bool {
+        let f = self.cave_frequency;
+        // Squash vertically so caverns are wider than they are tall
+        let value = self.cheese.get([x as f64 * f, y as f64 * f * 2.0, z as f64 * f]);
+        value > self.cave_threshold
+    }
+
+    fn is_tunnel(&self, x: i32, y: i32, z: i32) -> bool {
+        if self.tunnel_width <= 0.0 {
+            return false;
+        }
+        let f = self.tunnel_frequency;
+        let point = [x as f64 * f, y as f64 * f * 1.5, z as f64 * f];
+        let a = self.tunnel_a.get(point);
+        let b = self.tunnel_b.get(point);
+        a * a + b * b < self.tunnel_width * self.tunnel_width
+    }
+
+    /// Replays the worms of every chunk within reach, carving the parts that
+    /// pass through this chunk
+    fn carve_worms(&self, chunk: &mut Chunk, limits: &[[Option<i32>; CHUNK_SIZE]; CHUNK_SIZE]) {
+        let (chunk_x, chunk_z) = chunk.position;
+        let (min_x, min_z) = World::chunk_to_world(chunk.position, 0, 0);
+        let size = CHUNK_SIZE as f64;
+        let (min_x, min_z) = (min_x as f64, min_z as f64);
+
+        for origin_x in chunk_x - WORM_REACH..=chunk_x + WORM_REACH {
+            for origin_z in chunk_z - WORM_REACH..=chunk_z + WORM_REACH {
+                let mut rng = Rng::for_chunk(self.seed, (origin_x, origin_z), WORM_SALT);
+                if !rng.chance(self.worm_chance) {
+                    continue;
+                }
+                let (start_x, start_z) = World::chunk_to_world((origin_x, origin_z), 0, 0);
+                let start = [
+                    start_x as f64 + rng.next_f64() * size,
+                    rng.range(CAVE_FLOOR + 4, 40) as f64,
+                    start_z as f64 + rng.next_f64() * size,
+                ];
+                let thickness = 0.7 + rng.next_f64() * 0.8;
+                let mut position = start;
+                let mut yaw = rng.next_f64() * 2.0 * PI;
+                let mut pitch = (rng.next_f64() - 0.5) * 0.5;
+                let mut yaw_turn = 0.0;
+                let mut pitch_turn = 0.0;
+
+                for step in 0..self.worm_length {
+                    let t = step as f64 / self.worm_length as f64;
+                    let radius = 1.2 + (t * PI).sin() * 2.0 * thickness;
+
+                    position[0] += yaw.cos() * pitch.cos();
+                    position[1] += pitch.sin();
+                    position[2] += yaw.sin() * pitch.cos();
+
+                    pitch *= 0.7;
+                    pitch += pitch_turn * 0.1;
+                    yaw += yaw_turn * 0.1;
+                    pitch_turn = pitch_turn * 0.9 + (rng.next_f64() - rng.next_f64()) * 2.0;
+                    yaw_turn = yaw_turn * 0.75 + (rng.next_f64() - rng.next_f64()) * 4.0;
+
+                    let (dx, dz) = (position[0] - start[0], position[2] - start[2]);
+                    if dx.abs() > WORM_RANGE - radius || dz.abs() > WORM_RANGE - radius {
+                        break;
+                    }
+                    let outside = position[0] + radius < min_x
+                        || position[0] - radius > min_x + size
+                        || position[2] + radius < min_z
+                        || position[2] - radius > min_z + size;
+                    if !outside {
+                        let local = [position[0] - min_x, position[1], position[2] - min_z];
+                        carve_sphere(chunk, limits, local, radius);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Carves a sphere given in chunk-local coordinates, respecting column limits
+fn carve_sphere(
+    chunk: &mut Chunk,
+    limits: &[[Option<i32>; CHUNK_SIZE]; CHUNK_SIZE],
+    center: [f64; 3],
+    radius: f64,
+) {
+    let range = |center: f64, min: i32, max: i32| {
+        let low = ((center - radius).floor() as i32).max(min);
+        let high = ((center + radius).ceil() as i32).min(max - 1);
+        low..=high
+    };
+    for x in range(center[0], 0, CHUNK_SIZE as i32).map(|x| x as usize) {
+        for z in range(center[2], 0, CHUNK_SIZE as i32).map(|z| z as usize) {
+            let Some(limit) = limits[x][z] else { continue };
+            for y in range(center[1], CAVE_FLOOR, MAX_Y) {
+                if y > limit {
+                    continue;
+                }
+                let dx = x as f64 + 0.5 - center[0];
+                let dy = y as f64 + 0.5 - center[1];
+                let dz = z as f64 + 0.5 - center[2];
+                if dx * dx + dy * dy + dz * dz < radius * radius {
+                    carve_block(chunk, x, y, z);
+                }
+            }
+        }
+    }
+}
+
+fn carve_block(chunk: &mut Chunk, x: usize, y: i32, z: usize) {
+    let carvable = |block: BlockType| block.is_solid() && block != BlockType::Bedrock;
+    if chunk.get_block_at(x, y, z).is_some_and(carvable) {
+        chunk.set_block_at(x, y, z, BlockType::Air);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{ChunkBiomes, TerrainGenerator, WorldGenerator};
+    use super::*;
+
+    fn generate(generator: &TerrainGenerator, position: (i32, i32)) -> (Chunk, Heightmap) {
+        let mut chunk = Chunk::new(position);
+        generator.generate_chunk(&mut chunk);
+        let biomes = ChunkBiomes::new(generator.biomes(), position);
+        (chunk, generator.heightmap(position, &biomes))
+    }
+
+    #[test]
+    fn test_caves_carve_the_underground() {
+        let generator = TerrainGenerator::default();
+        let mut air = 0;
+        let mut deep_air = 0;
+        let mut underground = 0;
+        for position in [(0, 0), (3, -2), (-5, 7), (10, 10)] {
+            let (chunk, heights) = generate(&generator, position);
+            for x in 0..CHUNK_SIZE {
+                for z in 0..CHUNK_SIZE {
+                    let height = heights.get(x as i32, z as i32) as i32;
+                    assert_eq!(chunk.get_block_at(x, MIN_Y, z), Some(BlockType::Bedrock));
+                    for y in MIN_Y + 1..height {
+                        underground += 1;
+                        if chunk.get_block_at(x, y, z) == Some(BlockType::Air) {
+                            air += 1;
+                            assert!(y >= CAVE_FLOOR);
+                            if y < 0 {
+                                deep_air += 1;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        let ratio = air as f64 / underground as f64;
+        assert!(ratio > 0.01 && ratio < 0.3, "cave ratio {}", ratio);
+        assert!(deep_air > 0, "no caves below y = 0");
+    }
+
+    #[test]
+    fn test_caves_keep_sea_floor_sealed() {
+        let generator = TerrainGenerator::default();
+        let cover = generator.settings().cave_ocean_cover as usize;
+        let mut checked = 0;
+        for chunk_x in (-60..60).step_by(6) {
+            let position = (chunk_x, 0);
+            let (chunk, heights) = generate(&generator, position);
+            for x in 0..CHUNK_SIZE {
+                for z in 0..CHUNK_SIZE {
+                    let height = heights.get(x as i32, z as i32);
+                    if heights.water(x as i32, z as i32).is_none() {
+                        continue;
+                    }
+                    checked += 1;
+                    for y in height.saturating_sub(cover)..=height {
+                        let block = chunk.get_block(x, y, z).unwrap();
+                        assert!(block.is_solid(), "breach at {:?} in {:?}", (x, y, z), position);
+                    }
+                }
+            }
+        }
+        assert!(checked > 0, "no sea floor found");
+    }
+
+    #[test]
+    fn test_worms_cross_chunk_borders() {
+        let settings = GeneratorSettings {
+            cave_threshold: 2.0,
+            tunnel_width: 0.0,
+            worm_chance: 1.0,
+            ..Default::default()
+        };
+        let generator = TerrainGenerator::new(settings);
+        let (west, _) = generate(&generator, (0, 0));
+        let (east, heights) = generate(&generator, (1, 0));
+
+        let crossings = (CAVE_FLOOR..MAX_Y)
+            .flat_map(|y| (0..CHUNK_SIZE).map(move |z| (y, z)))
+            .filter(|&(y, z)| {
+                west.get_block_at(CHUNK_SIZE - 1, y, z) == Some(BlockType::Air)
+                    && east.get_block_at(0, y, z) == Some(BlockType::Air)
+                    && y < heights.get(0, z as i32) as i32
+            })
+            .count();
+        assert!(crossings > 0);
+    }
+}
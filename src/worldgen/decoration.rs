@@ -0,0 +1,280 @@
+use crate::block::BlockType;
+use crate::chunk::{Chunk, CHUNK_SIZE, MAX_Y};
+use crate::world::World;
+
+use super::random::{hash_position, Rng};
+use super::{Biome, GeneratorSettings};
+
+/// Furthest a tree's leaves reach from its trunk horizontally
+pub const TREE_RADIUS: i32 = 2;
+/// Tree positions rolled per chunk; the biome decides how many grow
+const TREE_ATTEMPTS: u32 = 12;
+/// Salt separating the tree random streams from other stages
+const TREE_SALT: u64 = 0x7472_6565;
+/// Salt for per-block plant and leaf rolls
+const PLANT_SALT: u32 = 0x706c_6e74;
+
+/// A tree rooted on the surface block at (x, ground, z)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tree {
+    pub x: i32,
+    pub ground: usize,
+    pub z: i32,
+    pub trunk_height: usize,
+}
+
+/// Places trees, tall grass and flowers on the surface of a chunk
+///
+/// Trees near a chunk edge spread their leaves into the neighbour. Instead
+/// of queueing those blocks until the neighbour is generated, every chunk
+/// replays the tree rolls of the chunks around it and stamps the parts of
+/// their trees that overlap it. The result only depends on the chunk
+/// position, so chunks can still be generated in any order on any thread.
+pub struct Decorator {
+    seed: u32,
+    tree_density: f64,
+    plant_density: f64,
+}
+
+impl Decorator {
+    pub fn new(settings: &GeneratorSettings) -> Self {
+        Self {
+            seed: settings.seed,
+            tree_density: settings.tree_density,
+            plant_density: settings.plant_density,
+        }
+    }
+
+    /// Decorates a chunk whose terrain is complete
+    ///
+    /// `site` returns the biome and surface height of a column within
+    /// [`TREE_RADIUS`] of the chunk, or `None` if nothing can grow there.
+    pub fn decorate(&self, chunk: &mut Chunk, site: impl Fn(i32, i32) -> Option<(Biome, usize)>) {
+        let (chunk_x, chunk_z) = chunk.position;
+        for origin_x in chunk_x - 1..=chunk_x + 1 {
+            for origin_z in chunk_z - 1..=chunk_z + 1 {
+                for tree in self.trees_in((origin_x, origin_z), chunk.position, &site) {
+                    stamp_tree(chunk, tree, self.seed);
+                }
+            }
+        }
+        self.plant(chunk, &site);
+    }
+
+    /// Trees rooted in chunk `origin` whose leaves can reach chunk `target`
+    pub fn trees_in(
+        &self,
+        origin: (i32, i32),
+        target: (i32, i32),
+        site: impl Fn(i32, i32) -> Option<(Biome, usize)>,
+    ) -> Vec<Tree> {
+        let (min_x, min_z) = World::chunk_to_world(target, 0, 0);
+        let size = CHUNK_SIZE as i32;
+        let reaches = |x: i32, z: i32| {
+            x >= min_x - TREE_RADIUS
+                && x < min_x + size + TREE_RADIUS
+                && z >= min_z - TREE_RADIUS
+                && z < min_z + size + TREE_RADIUS
+        };
+
+        let mut rng = Rng::for_chunk(self.seed, origin, TREE_SALT);
+        let mut trees = Vec::new();
+        for _ in 0..TREE_ATTEMPTS {
+            // Draw every value up front so later attempts never depend on
+            // whether earlier ones grew
+            let (local_x, local_z) = (rng.range(0, size) as usize, rng.range(0, size) as usize);
+            let (x, z) = World::chunk_to_world(origin, local_x, local_z);
+            let roll = rng.next_f64();
+            let trunk_height = rng.range(4, 7) as usize;
+
+            if !reaches(x, z) {
+                continue;
+            }
+            let Some((biome, ground)) = site(x, z) else { continue };
+            if roll >= biome.tree_chance() * self.tree_density {
+                continue;
+            }
+            if ground + trunk_height + 3 >= MAX_Y as usize {
+                continue;
+            }
+            trees.push(Tree { x, ground, z, trunk_height });
+        }
+        trees
+    }
+
+    /// Scatters tall grass and flowers on the grass blocks of a chunk
+    fn plant(&self, chunk: &mut Chunk, site: impl Fn(i32, i32) -> Option<(Biome, usize)>) {
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                let (world_x, world_z) = World::chunk_to_world(chunk.position, x, z);
+                let Some((biome, ground)) = site(world_x, world_z) else { continue };
+                if chunk.get_block(x, ground, z) != Some(BlockType::Grass)
+                    || chunk.get_block(x, ground + 1, z) != Some(BlockType::Air)
+                {
+                    continue;
+                }
+
+                let (grass, flowers) = biome.plant_chances();
+                let hash = hash_position(self.seed ^ PLANT_SALT, world_x, ground as i32, world_z);
+                let roll = unit(hash);
+                let plant = if roll < flowers * self.plant_density {
+                    BlockType::Flower
+                } else if roll < (flowers + grass) * self.plant_density {
+                    BlockType::TallGrass
+                } else {
+                    continue;
+                };
+                chunk.set_block(x, ground + 1, z, plant);
+            }
+        }
+    }
+}
+
+/// Writes the blocks of `tree` that fall inside `chunk`
+///
+/// Logs overwrite leaves and leaves only fill air, so overlapping trees give
+/// the same blocks whichever is stamped first.
+fn stamp_tree(chunk: &mut Chunk, tree: Tree, seed: u32) {
+    let (min_x, min_z) = World::chunk_to_world(chunk.position, 0, 0);
+    let local = |x: i32, z: i32| {
+        let (x, z) = (x - min_x, z - min_z);
+        let inside = (0..CHUNK_SIZE as i32).contains(&x) && (0..CHUNK_SIZE as i32).contains(&z);
+        inside.then_some((x as usize, z as usize))
+    };
+    let top = tree.ground + tree.trunk_height;
+
+    for y in top - 1..=top + 2 {
+        let radius = if y < top + 1 { TREE_RADIUS } else { 1 };
+        for dx in -radius..=radius {
+            for dz in -radius..=radius {
+                let (x, z) = (tree.x + dx, tree.z + dz);
+                let Some((local_x, local_z)) = local(x, z) else { continue };
+                let corner = dx.abs() == radius && dz.abs() == radius;
+                // The crown is a plus shape; lower corners are trimmed at random
+                let trimmed = || y == top + 2 || hash_position(seed ^ PLANT_SALT, x, y as i32, z) & 1 == 0;
+                if corner && trimmed() {
+                    continue;
+                }
+                if chunk.get_block(local_x, y, local_z) == Some(BlockType::Air) {
+                    chunk.set_block(local_x, y, local_z, BlockType::Leaves);
+                }
+            }
+        }
+    }
+
+    if let Some((x, z)) = local(tree.x, tree.z) {
+        // A cave may have opened under the trunk; keep the tree grounded
+        let ground = chunk.get_block(x, tree.ground, z);
+        if ground.is_some_and(|block| !block.is_solid() || block == BlockType::Grass) {
+            chunk.set_block(x, tree.ground, z, BlockType::Dirt);
+        }
+        for y in tree.ground + 1..=top {
+            chunk.set_block(x, y, z, BlockType::Log);
+        }
+    }
+}
+
+/// Maps a hash to [0, 1)
+fn unit(hash: u64) -> f64 {
+    (hash >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{TerrainGenerator, WorldGenerator};
+    use super::*;
+
+    /// Generator without structures, which may overwrite trees
+    fn tree_generator() -> TerrainGenerator {
+        TerrainGenerator::new(GeneratorSettings { structures: false, ..Default::default() })
+    }
+
+    /// Generates a square of chunks around a forest chunk
+    fn forest_world(generator: &TerrainGenerator) -> (World, (i32, i32)) {
+        let center = (-40..40)
+            .flat_map(|x| (-40..40).map(move |z| (x, z)))
+            .find(|&(x, z)| generator.biomes().biome_at(x * 16 + 8, z * 16 + 8) == Biome::Forest)
+            .expect("no forest found");
+        let mut world = World::new();
+        for dx in -2..=2 {
+            for dz in -2..=2 {
+                let mut chunk = Chunk::new((center.0 + dx, center.1 + dz));
+                generator.generate_chunk(&mut chunk);
+                world.insert_chunk(chunk);
+            }
+        }
+        (world, center)
+    }
+
+    /// Trunks in the middle 3x3 chunks, as (x, lowest log, highest log, z)
+    fn trunks(world: &World, center: (i32, i32)) -> Vec<(i32, i32, i32, i32)> {
+        let (min_x, min_z) = World::chunk_to_world((center.0 - 1, center.1 - 1), 0, 0);
+        let mut trunks = Vec::new();
+        for x in min_x..min_x + 48 {
+            for z in min_z..min_z + 48 {
+                let logs: Vec<i32> = (0..MAX_Y)
+                    .filter(|&y| world.get_block(x, y, z) == Some(BlockType::Log))
+                    .collect();
+                if let (Some(&low), Some(&high)) = (logs.first(), logs.last()) {
+                    trunks.push((x, low, high, z));
+                }
+            }
+        }
+        trunks
+    }
+
+    #[test]
+    fn test_trees_are_never_clipped_at_chunk_borders() {
+        let generator = tree_generator();
+        let (world, center) = forest_world(&generator);
+        let trunks = trunks(&world, center);
+        assert!(trunks.len() > 5, "only {} trees", trunks.len());
+
+        let mut on_border = 0;
+        for &(x, _, top, z) in &trunks {
+            let local = World::world_to_chunk(x, z).1;
+            let radius = TREE_RADIUS as usize;
+            let near_edge = |v: usize| v < radius || v >= CHUNK_SIZE - radius;
+            if near_edge(local.0) || near_edge(local.1) {
+                on_border += 1;
+            }
+            // The untrimmed parts of the canopy must exist on both sides of the seam
+            let canopy = [(1, 0, 0), (-1, 0, 0), (2, 0, 0), (0, 0, 2)];
+            let crown = [(0, -1, -2), (0, 1, 1), (0, 2, 0)];
+            for (dx, dy, dz) in canopy.into_iter().chain(crown) {
+                let block = world.get_block(x + dx, top + dy, z + dz);
+                let at = ((x, top, z), (dx, dy, dz));
+                let filled = block.is_some_and(|block| block != BlockType::Air);
+                assert!(filled, "gap in canopy {:?}", at);
+            }
+        }
+        assert!(on_border > 0);
+    }
+
+    #[test]
+    fn test_trees_and_plants_grow_on_land() {
+        let generator = tree_generator();
+        let sea_level = generator.settings().sea_level;
+        let (world, center) = forest_world(&generator);
+        for (x, low, _, z) in trunks(&world, center) {
+            assert_eq!(world.get_block(x, low - 1, z), Some(BlockType::Dirt));
+            assert!(low > sea_level);
+        }
+
+        let mut plants = 0;
+        for chunk in world.chunks() {
+            for x in 0..CHUNK_SIZE {
+                for y in 1..MAX_Y as usize {
+                    for z in 0..CHUNK_SIZE {
+                        let block = chunk.get_block(x, y, z).unwrap();
+                        if matches!(block, BlockType::TallGrass | BlockType::Flower) {
+                            plants += 1;
+                            assert_eq!(chunk.get_block(x, y - 1, z), Some(BlockType::Grass));
+                        }
+                    }
+                }
+            }
+        }
+        assert!(plants > 0);
+    }
+}
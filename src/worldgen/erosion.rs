@@ -0,0 +1,483 @@
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::chunk::CHUNK_SIZE;
+
+use super::random::Rng;
+use super::{smoothstep, GeneratorSettings};
+
+/// Width of an erosion region in blocks
+pub const REGION_SIZE: i32 = 128;
+/// Extra terrain simulated around a region so droplets can enter from outside
+pub const REGION_MARGIN: i32 = CHUNK_SIZE as i32;
+/// Distance over which erosion fades out towards a region's edge
+const EDGE_FADE: f64 = 24.0;
+/// Regions kept in memory before the furthest is dropped
+const MAX_CACHED_REGIONS: usize = 16;
+/// Salt separating the droplet random streams from other stages
+const EROSION_SALT: u64 = 0x6572_6f64;
+/// Magic bytes and version at the start of a region file
+const FILE_MAGIC: &[u8; 4] = b"EROS";
+const FILE_VERSION: u32 = 1;
+
+// Droplet simulation constants
+const INERTIA: f32 = 0.05;
+const CAPACITY: f32 = 4.0;
+const MIN_SLOPE: f32 = 0.01;
+const DEPOSIT_RATE: f32 = 0.3;
+const ERODE_RATE: f32 = 0.3;
+const EVAPORATION: f32 = 0.02;
+const GRAVITY: f32 = 4.0;
+const MAX_STEPS: usize = 48;
+const BRUSH_RADIUS: i32 = 2;
+
+/// Height changes from eroding one region, added on top of the noise terrain
+///
+/// Changes fade to zero towards the region's edges, so neighbouring regions
+/// are eroded independently without leaving seams.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErosionRegion {
+    position: (i32, i32),
+    fingerprint: u64,
+    deltas: Vec<f32>,
+}
+
+impl ErosionRegion {
+    /// Region holding the column at world coordinates (x, z)
+    pub fn containing(x: i32, z: i32) -> (i32, i32) {
+        (x.div_euclid(REGION_SIZE), z.div_euclid(REGION_SIZE))
+    }
+
+    /// World coordinates of the region's first column
+    pub fn origin(position: (i32, i32)) -> (i32, i32) {
+        (position.0 * REGION_SIZE, position.1 * REGION_SIZE)
+    }
+
+    pub fn position(&self) -> (i32, i32) {
+        self.position
+    }
+
+    /// Height change of a column inside the region, in blocks
+    pub fn delta(&self, x: i32, z: i32) -> f64 {
+        let (min_x, min_z) = Self::origin(self.position);
+        let index = (x - min_x) as usize * REGION_SIZE as usize + (z - min_z) as usize;
+        self.deltas[index] as f64
+    }
+
+    /// Largest amount any column was lowered and raised by
+    pub fn extremes(&self) -> (f64, f64) {
+        let carved = self.deltas.iter().fold(0.0f32, |low, &d| low.min(d));
+        let deposited = self.deltas.iter().fold(0.0f32, |high, &d| high.max(d));
+        (-carved as f64, deposited as f64)
+    }
+
+    pub fn file_name(position: (i32, i32)) -> String {
+        format!("r.{}.{}.ero", position.0, position.1)
+    }
+
+    /// Writes the region into `dir`
+    pub fn save(&self, dir: impl AsRef<Path>) -> io::Result<()> {
+        let mut bytes = Vec::with_capacity(28 + self.deltas.len() * 4);
+        bytes.extend_from_slice(FILE_MAGIC);
+        bytes.extend_from_slice(&FILE_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&self.fingerprint.to_le_bytes());
+        bytes.extend_from_slice(&self.position.0.to_le_bytes());
+        bytes.extend_from_slice(&self.position.1.to_le_bytes());
+        bytes.extend_from_slice(&(REGION_SIZE as u32).to_le_bytes());
+        for delta in &self.deltas {
+            bytes.extend_from_slice(&delta.to_le_bytes());
+        }
+        std::fs::write(dir.as_ref().join(Self::file_name(self.position)), bytes)
+    }
+
+    /// Reads a region saved in `dir`
+    ///
+    /// Returns `None` if there is no file or it was made with other settings.
+    pub fn load(
+        dir: impl AsRef<Path>,
+        position: (i32, i32),
+        fingerprint: u64,
+    ) -> io::Result<Option<Self>> {
+        let bytes = match std::fs::read(dir.as_ref().join(Self::file_name(position))) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let cells = (REGION_SIZE * REGION_SIZE) as usize;
+        if bytes.len() != 28 + cells * 4 || &bytes[0..4] != FILE_MAGIC {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not an erosion region"));
+        }
+        let u32_at = |i: usize| u32::from_le_bytes(bytes[i..i + 4].try_into().unwrap());
+        let stored = u64::from_le_bytes(bytes[8..16].try_into().unwrap());
+        let stored_position = (u32_at(16) as i32, u32_at(20) as i32);
+        if u32_at(4) != FILE_VERSION || u32_at(24) != REGION_SIZE as u32 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "unsupported region format"));
+        }
+        if stored != fingerprint || stored_position != position {
+            return Ok(None);
+        }
+        let deltas = bytes[28..]
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+            .collect();
+        Ok(Some(Self { position, fingerprint, deltas }))
+    }
+}
+
+/// Hash of the settings a region depends on, stored in region files
+///
+/// Covers only what the noise heights and the simulation read, so tuning
+/// caves, ores, water or decoration keeps precomputed regions valid.
+pub fn fingerprint(settings: &GeneratorSettings) -> u64 {
+    let s = settings;
+    let integers = [s.seed, s.octaves, s.erosion_droplets, s.erosion_thermal_passes];
+    let floats = [
+        s.frequency,
+        s.lacunarity,
+        s.persistence,
+        s.amplitude,
+        s.base_height,
+        s.warp_strength,
+        s.warp_frequency,
+        s.mountain_frequency,
+        s.mountain_height,
+        s.mountain_mask_frequency,
+        s.plateau_height,
+        s.plateau_falloff,
+        s.biome_frequency,
+        s.continent_frequency,
+        s.erosion_talus,
+    ];
+    let bytes = integers
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .chain(floats.iter().flat_map(|value| value.to_bits().to_le_bytes()));
+    bytes.fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Erodes terrain one region at a time and caches the results
+///
+/// Each region is simulated on its own, from the noise terrain of the region
+/// plus a margin, with droplets drawn from a random stream seeded by the
+/// region position. Regions are therefore deterministic and can be computed
+/// ahead of time and loaded from `erosion_cache`. Cache files that cannot
+/// be read are simulated over, and their errors kept for
+/// [`Eroder::take_cache_errors`].
+pub struct Eroder {
+    seed: u32,
+    droplets: u32,
+    thermal_passes: u32,
+    talus: f32,
+    fingerprint: u64,
+    cache_dir: Option<PathBuf>,
+    cache_errors: Mutex<Vec<((i32, i32), io::Error)>>,
+    regions: Mutex<HashMap<(i32, i32), Arc<ErosionRegion>>>,
+}
+
+impl Eroder {
+    pub fn new(settings: &GeneratorSettings) -> Self {
+        Self {
+            seed: settings.seed,
+            droplets: settings.erosion_droplets,
+            thermal_passes: settings.erosion_thermal_passes,
+            talus: settings.erosion_talus as f32,
+            fingerprint: fingerprint(settings),
+            cache_dir: settings.erosion_cache.as_ref().map(PathBuf::from),
+            cache_errors: Mutex::new(Vec::new()),
+            regions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the eroded region, loading or simulating it if needed
+    ///
+    /// `terrain` produces the noise heights of the region and its margin,
+    /// as taken by [`Eroder::erode_region`].
+    pub fn region(
+        &self,
+        position: (i32, i32),
+        terrain: impl FnOnce() -> Vec<f32>,
+    ) -> Arc<ErosionRegion> {
+        if let Some(region) = self.regions.lock().unwrap().get(&position) {
+            return region.clone();
+        }
+
+        // Simulate without holding the lock; a region computed twice by
+        // racing workers comes out the same either way
+        let loaded = self.cache_dir.as_ref().and_then(|dir| {
+            ErosionRegion::load(dir, position, self.fingerprint).unwrap_or_else(|e| {
+                self.cache_errors.lock().unwrap().push((position, e));
+                None
+            })
+        });
+        let region = Arc::new(loaded.unwrap_or_else(|| self.erode_region(position, terrain())));
+
+        let mut regions = self.regions.lock().unwrap();
+        if regions.len() >= MAX_CACHED_REGIONS {
+            let distance = |p: &(i32, i32)| (p.0 - position.0).abs() + (p.1 - position.1).abs();
+            if let Some(furthest) = regions.keys().max_by_key(|p| distance(p)).copied() {
+                regions.remove(&furthest);
+            }
+        }
+        regions.entry(position).or_insert(region).clone()
+    }
+
+    /// Regions whose cache file could not be read since the last call, with
+    /// the errors
+    pub fn take_cache_errors(&self) -> Vec<((i32, i32), io::Error)> {
+        std::mem::take(&mut *self.cache_errors.lock().unwrap())
+    }
+
+    /// Simulates erosion on the noise heights of a region and its margin
+    ///
+    /// `heights` covers `REGION_SIZE + 2 * REGION_MARGIN` columns along each
+    /// axis, starting at the margin's corner and indexed `[x * side + z]`.
+    pub fn erode_region(&self, position: (i32, i32), mut heights: Vec<f32>) -> ErosionRegion {
+        let side = (REGION_SIZE + 2 * REGION_MARGIN) as usize;
+        assert_eq!(heights.len(), side * side);
+        let original = heights.clone();
+
+        let mut rng = Rng::for_chunk(self.seed, position, EROSION_SALT);
+        hydraulic(&mut heights, side, self.droplets, &mut rng);
+        thermal(&mut heights, side, self.thermal_passes, self.talus);
+
+        let size = REGION_SIZE as usize;
+        let margin = REGION_MARGIN as usize;
+        let mut deltas = Vec::with_capacity(size * size);
+        for x in 0..size {
+            for z in 0..size {
+                let edge = x.min(z).min(size - 1 - x).min(size - 1 - z);
+                let fade = smoothstep(0.0, EDGE_FADE, edge as f64) as f32;
+                let index = (x + margin) * side + z + margin;
+                deltas.push((heights[index] - original[index]) * fade);
+            }
+        }
+        ErosionRegion { position, fingerprint: self.fingerprint, deltas }
+    }
+}
+
+/// Height and gradient at a point, interpolated from the four cells around it
+fn gradient(heights: &[f32], side: usize, x: f32, z: f32) -> (f32, f32, f32) {
+    let (cell_x, cell_z) = (x as usize, z as usize);
+    let (u, v) = (x - cell_x as f32, z - cell_z as f32);
+    let index = cell_x * side + cell_z;
+    let (h00, h01) = (heights[index], heights[index + 1]);
+    let (h10, h11) = (heights[index + side], heights[index + side + 1]);
+
+    let gradient_x = (h10 - h00) * (1.0 - v) + (h11 - h01) * v;
+    let gradient_z = (h01 - h00) * (1.0 - u) + (h11 - h10) * u;
+    let height =
+        h00 * (1.0 - u) * (1.0 - v) + h10 * u * (1.0 - v) + h01 * (1.0 - u) * v + h11 * u * v;
+    (height, gradient_x, gradient_z)
+}
+
+/// Rolls water droplets downhill, eroding steep ground and depositing
+/// sediment where they slow down
+fn hydraulic(heights: &mut [f32], side: usize, droplets: u32, rng: &mut Rng) {
+    // Erosion is spread over a small disc so channels are not one block wide
+    let mut brush = Vec::new();
+    for dx in -BRUSH_RADIUS..=BRUSH_RADIUS {
+        for dz in -BRUSH_RADIUS..=BRUSH_RADIUS {
+            let weight = BRUSH_RADIUS as f32 - ((dx * dx + dz * dz) as f32).sqrt();
+            if weight > 0.0 {
+                brush.push((dx, dz, weight));
+            }
+        }
+    }
+    let total: f32 = brush.iter().map(|&(_, _, w)| w).sum();
+    let limit = (side - 1) as f32;
+
+    for _ in 0..droplets {
+        let mut x = rng.next_f64() as f32 * limit;
+        let mut z = rng.next_f64() as f32 * limit;
+        let (mut dir_x, mut dir_z) = (0.0f32, 0.0f32);
+        let (mut speed, mut water, mut sediment) = (1.0f32, 1.0f32, 0.0f32);
+
+        for _ in 0..MAX_STEPS {
+            let (cell_x, cell_z) = (x as usize, z as usize);
+            let (u, v) = (x - cell_x as f32, z - cell_z as f32);
+            let (height, gradient_x, gradient_z) = gradient(heights, side, x, z);
+
+            dir_x = dir_x * INERTIA - gradient_x * (1.0 - INERTIA);
+            dir_z = dir_z * INERTIA - gradient_z * (1.0 - INERTIA);
+            let length = (dir_x * dir_x + dir_z * dir_z).sqrt();
+            if length < f32::EPSILON {
+                break;
+            }
+            x += dir_x / length;
+            z += dir_z / length;
+            if !(0.0..limit).contains(&x) || !(0.0..limit).contains(&z) {
+                break;
+            }
+
+            let drop = gradient(heights, side, x, z).0 - height;
+            let capacity = (-drop).max(MIN_SLOPE) * speed * water * CAPACITY;
+            if sediment > capacity || drop > 0.0 {
+                // Fill pits uphill, otherwise drop what the water cannot carry
+                let amount = if drop > 0.0 {
+                    drop.min(sediment)
+                } else {
+                    (sediment - capacity) * DEPOSIT_RATE
+                };
+                sediment -= amount;
+                let index = cell_x * side + cell_z;
+                heights[index] += amount * (1.0 - u) * (1.0 - v);
+                heights[index + side] += amount * u * (1.0 - v);
+                heights[index + 1] += amount * (1.0 - u) * v;
+                heights[index + side + 1] += amount * u * v;
+            } else {
+                let amount = ((capacity - sediment) * ERODE_RATE).min(-drop);
+                for &(dx, dz, weight) in &brush {
+                    let (bx, bz) = (cell_x as i32 + dx, cell_z as i32 + dz);
+                    if bx < 0 || bz < 0 || bx >= side as i32 || bz >= side as i32 {
+                        continue;
+                    }
+                    let index = bx as usize * side + bz as usize;
+                    let removed = (amount * weight / total).min(heights[index]);
+                    heights[index] -= removed;
+                    sediment += removed;
+                }
+            }
+
+            speed = (speed * speed - drop * GRAVITY).max(0.0).sqrt();
+            water *= 1.0 - EVAPORATION;
+        }
+    }
+}
+
+/// Slides material off slopes steeper than `talus` onto the lowest neighbour
+fn thermal(heights: &mut [f32], side: usize, passes: u32, talus: f32) {
+    for _ in 0..passes {
+        for x in 1..side - 1 {
+            for z in 1..side - 1 {
+                let index = x * side + z;
+                let lowest = [index - side, index + side, index - 1, index + 1]
+                    .into_iter()
+                    .min_by(|&a, &b| heights[a].total_cmp(&heights[b]))
+                    .unwrap();
+                let difference = heights[index] - heights[lowest];
+                if difference > talus {
+                    let moved = (difference - talus) * 0.25;
+                    heights[index] -= moved;
+                    heights[lowest] += moved;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIDE: usize = (REGION_SIZE + 2 * REGION_MARGIN) as usize;
+
+    /// A bumpy slope falling towards +X
+    fn slope() -> Vec<f32> {
+        let mut heights = Vec::with_capacity(SIDE * SIDE);
+        for x in 0..SIDE {
+            for z in 0..SIDE {
+                let bumps = ((x as f32 * 0.3).sin() + (z as f32 * 0.21).cos()) * 2.0;
+                heights.push(60.0 - x as f32 * 0.3 + bumps);
+            }
+        }
+        heights
+    }
+
+    fn eroder(seed: u32) -> Eroder {
+        Eroder::new(&GeneratorSettings {
+            seed,
+            erosion_droplets: 4000,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_erosion_is_deterministic() {
+        let a = eroder(3).erode_region((1, -2), slope());
+        let b = eroder(3).erode_region((1, -2), slope());
+        assert_eq!(a, b);
+        assert_ne!(a, eroder(4).erode_region((1, -2), slope()));
+    }
+
+    #[test]
+    fn test_erosion_carves_and_deposits() {
+        let region = eroder(3).erode_region((0, 0), slope());
+        let (carved, deposited) = region.extremes();
+        assert!(carved > 0.5, "carved {}", carved);
+        assert!(deposited > 0.1, "deposited {}", deposited);
+
+        // Nothing changes on the region's edges, so regions meet seamlessly
+        for i in 0..REGION_SIZE {
+            for (x, z) in [(i, 0), (0, i), (i, REGION_SIZE - 1), (REGION_SIZE - 1, i)] {
+                assert_eq!(region.delta(x, z), 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_fingerprint_ignores_unrelated_settings() {
+        let settings = GeneratorSettings::default();
+        let base = fingerprint(&settings);
+        let unrelated = GeneratorSettings {
+            caves: false,
+            sea_level: settings.sea_level + 5,
+            tree_density: 0.0,
+            ores: Vec::new(),
+            structures: false,
+            erosion_cache: Some("elsewhere".to_string()),
+            ..settings.clone()
+        };
+        assert_eq!(fingerprint(&unrelated), base);
+
+        let hillier = GeneratorSettings { amplitude: settings.amplitude + 1.0, ..settings.clone() };
+        let steeper = GeneratorSettings { erosion_talus: settings.erosion_talus * 2.0, ..settings };
+        assert_ne!(fingerprint(&hillier), base);
+        assert_ne!(fingerprint(&steeper), base);
+    }
+
+    #[test]
+    fn test_region_file_round_trip() {
+        let dir = std::env::temp_dir().join(format!("erosion-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let region = eroder(3).erode_region((-1, 5), slope());
+        region.save(&dir).unwrap();
+
+        let loaded = ErosionRegion::load(&dir, (-1, 5), region.fingerprint).unwrap();
+        assert_eq!(loaded.as_ref(), Some(&region));
+        // Regions made with other settings are ignored
+        assert_eq!(ErosionRegion::load(&dir, (-1, 5), region.fingerprint ^ 1).unwrap(), None);
+        assert_eq!(ErosionRegion::load(&dir, (0, 0), region.fingerprint).unwrap(), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_unreadable_cache_files_are_reported() {
+        let dir = std::env::temp_dir().join(format!("erosion-cache-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join(ErosionRegion::file_name((2, 0))), b"EROS truncated").unwrap();
+        let eroder = Eroder::new(&GeneratorSettings {
+            seed: 3,
+            erosion_droplets: 4000,
+            erosion_cache: Some(dir.to_string_lossy().into_owned()),
+            ..Default::default()
+        });
+
+        // The region is simulated instead, and the error kept for the caller
+        let region = eroder.region((2, 0), slope);
+        assert_eq!(*region, eroder.erode_region((2, 0), slope()));
+        let errors = eroder.take_cache_errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, (2, 0));
+        assert_eq!(errors[0].1.kind(), io::ErrorKind::InvalidData);
+        assert!(eroder.take_cache_errors().is_empty());
+
+        // Missing files are not errors
+        eroder.region((3, 0), slope);
+        assert!(eroder.take_cache_errors().is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,127 @@
+use noise::{NoiseFn, Perlin};
+
+/// Multi-octave noise built on a single Perlin source
+#[derive(Clone)]
+pub struct FractalNoise {
+    perlin: Perlin,
+    pub octaves: u32,
+    /// Frequency of the first octave, in cycles per block
+    pub frequency: f64,
+    /// Frequency multiplier between octaves
+    pub lacunarity: f64,
+    /// Amplitude multiplier between octaves
+    pub persistence: f64,
+}
+
+impl FractalNoise {
+    pub fn new(seed: u32, octaves: u32, frequency: f64) -> Self {
+        Self {
+            perlin: Perlin::new(seed),
+            octaves: octaves.max(1),
+            frequency,
+            lacunarity: 2.0,
+            persistence: 0.5,
+        }
+    }
+
+    /// Fractional Brownian motion: summed octaves normalised to roughly [-1, 1]
+    pub fn fbm(&self, x: f64, z: f64) -> f64 {
+        let mut frequency = self.frequency;
+        let mut amplitude = 1.0;
+        let mut total = 0.0;
+        let mut max = 0.0;
+        for _ in 0..self.octaves {
+            total += self.perlin.get([x * frequency, z * frequency]) * amplitude;
+            max += amplitude;
+            frequency *= self.lacunarity;
+            amplitude *= self.persistence;
+        }
+        total / max
+    }
+
+    /// Ridged multifractal noise in [0, 1], with sharp crests where the
+    /// underlying noise crosses zero
+    ///
+    /// Each octave is weighted by the previous one, so detail gathers along
+    /// the ridges and valleys between them stay smooth.
+    pub fn ridged(&self, x: f64, z: f64) -> f64 {
+        let mut frequency = self.frequency;
+        let mut amplitude = 1.0;
+        let mut weight = 1.0;
+        let mut total = 0.0;
+        let mut max = 0.0;
+        for _ in 0..self.octaves {
+            let ridge = 1.0 - self.perlin.get([x * frequency, z * frequency]).abs();
+            let ridge = ridge * ridge * weight;
+            weight = ridge.clamp(0.0, 1.0);
+            total += ridge * amplitude;
+            max += amplitude;
+            frequency *= self.lacunarity;
+            amplitude *= self.persistence;
+        }
+        (total / max).clamp(0.0, 1.0)
+    }
+}
+
+/// Offsets sample coordinates by two independent noise fields, bending the
+/// straight features of the noise it feeds into organic shapes
+#[derive(Clone)]
+pub struct DomainWarp {
+    offset_x: FractalNoise,
+    offset_z: FractalNoise,
+    /// Maximum displacement in blocks
+    pub strength: f64,
+}
+
+impl DomainWarp {
+    pub fn new(seed: u32, frequency: f64, strength: f64) -> Self {
+        Self {
+            offset_x: FractalNoise::new(seed, 3, frequency),
+            offset_z: FractalNoise::new(seed.wrapping_add(1), 3, frequency),
+            strength,
+        }
+    }
+
+    /// Returns the warped position to sample instead of (x, z)
+    pub fn warp(&self, x: f64, z: f64) -> (f64, f64) {
+        if self.strength == 0.0 {
+            return (x, z);
+        }
+        (
+            x + self.offset_x.fbm(x, z) * self.strength,
+            z + self.offset_z.fbm(x, z) * self.strength,
+        )
+    }
+}
+
+/// Smooth Hermite step from 0 at `edge0` to 1 at `edge1`
+pub fn smoothstep(edge0: f64, edge1: f64, x: f64) -> f64 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fractal_ranges() {
+        let noise = FractalNoise::new(3, 5, 0.02);
+        for i in 0..500 {
+            let (x, z) = (i as f64 * 7.3 - 900.0, i as f64 * -3.1 + 40.0);
+            assert!((-1.0..=1.0).contains(&noise.fbm(x, z)));
+            assert!((0.0..=1.0).contains(&noise.ridged(x, z)));
+        }
+    }
+
+    #[test]
+    fn test_warp_is_bounded_and_optional() {
+        let warp = DomainWarp::new(11, 0.01, 20.0);
+        let (x, z) = warp.warp(100.0, -50.0);
+        assert!((x - 100.0).abs() <= 20.0 && (z + 50.0).abs() <= 20.0);
+        assert_ne!((x, z), (100.0, -50.0));
+
+        let none = DomainWarp::new(11, 0.01, 0.0);
+        assert_eq!(none.warp(100.0, -50.0), (100.0, -50.0));
+    }
+}
@@ -0,0 +1,225 @@
+use std::collections::HashMap;
+use std::f64::consts::TAU;
+use std::sync::Mutex;
+
+use super::random::Rng;
+use super::{smoothstep, FractalNoise, GeneratorSettings};
+
+/// Width in blocks of the grid cells that each hold at most one lake
+pub const LAKE_CELL: i32 = 64;
+/// Smallest lake radius in blocks
+const LAKE_MIN_RADIUS: f64 = 5.0;
+/// Width of the slope rising from a lake's edge to the surrounding land
+const LAKE_SHORE: f64 = 4.0;
+/// Depth of a lake's bowl at its centre, below the water surface
+const LAKE_DEPTH: f64 = 4.0;
+/// Largest height difference around a lake's rim; steeper sites get no lake
+const LAKE_MAX_SLOPE: f64 = 6.0;
+/// Points sampled around a lake's rim to find where it would overflow
+const RIM_SAMPLES: usize = 16;
+/// Depth of a river channel below its water surface at the centre line
+const CHANNEL_DEPTH: f64 = 2.0;
+/// How far below `river_max_height` rivers start to thin out
+const RIVER_FADE: f64 = 12.0;
+/// Lake cells kept in memory before the cache is cleared
+const MAX_CACHED_LAKES: usize = 1024;
+/// Salt separating the lake random streams from other stages
+const LAKE_SALT: u64 = 0x6c61_6b65;
+
+/// A lake filling a bowl up to the lowest point of its rim
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Lake {
+    pub x: f64,
+    pub z: f64,
+    pub radius: f64,
+    /// Height of the water surface
+    pub level: f64,
+}
+
+/// Shapes river channels and lakes into the terrain
+///
+/// Rivers follow the lines where a low-frequency noise crosses zero, so a
+/// river is a continuous curve across the world rather than something that
+/// has to be traced from chunk to chunk. Lakes sit on a coarse grid: each
+/// cell may hold one lake, placed from the cell's own random stream and
+/// sized to stay inside the cell. Both only depend on the seed and the
+/// terrain heights, so every chunk sees the same rivers and lakes.
+pub struct Hydrology {
+    seed: u32,
+    sea_level: f64,
+    rivers: Option<FractalNoise>,
+    river_width: f64,
+    river_bank_width: f64,
+    river_depth: f64,
+    river_max_height: f64,
+    lake_chance: f64,
+    lake_radius: f64,
+    lakes: Mutex<HashMap<(i32, i32), Option<Lake>>>,
+}
+
+impl Hydrology {
+    pub fn new(settings: &GeneratorSettings) -> Self {
+        let seed = settings.seed;
+        let rivers = settings
+            .rivers
+            .then(|| FractalNoise::new(seed.wrapping_add(30), 3, settings.river_frequency));
+        // Lakes and their shores must fit inside one cell
+        let largest = LAKE_CELL as f64 / 2.0 - LAKE_SHORE - 3.0;
+        Self {
+            seed,
+            sea_level: settings.sea_level as f64,
+            rivers,
+            river_width: settings.river_width,
+            river_bank_width: settings.river_bank_width,
+            river_depth: settings.river_depth,
+            river_max_height: settings.river_max_height,
+            lake_chance: if settings.lakes { settings.lake_chance } else { 0.0 },
+            lake_radius: settings.lake_radius.clamp(LAKE_MIN_RADIUS, largest),
+            lakes: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Surface height and water level of a column
+    ///
+    /// `terrain` is the column's height before rivers and lakes, and
+    /// `terrain_at` gives that height for any other column.
+    pub fn shape(
+        &self,
+        x: i32,
+        z: i32,
+        terrain: f64,
+        terrain_at: impl Fn(i32, i32) -> f64,
+    ) -> (f64, Option<f64>) {
+        let (mut height, mut water) = self.river(x, z, terrain);
+
+        let cell = (x.div_euclid(LAKE_CELL), z.div_euclid(LAKE_CELL));
+        if let Some(lake) = self.lake(cell, terrain_at) {
+            let distance = ((x as f64 - lake.x).powi(2) + (z as f64 - lake.z).powi(2)).sqrt();
+            if distance < lake.radius + LAKE_SHORE {
+                let depth = 1.0 - (distance / lake.radius).min(1.0).powi(2);
+                let shore = (distance - lake.radius).max(0.0);
+                height = height.min(lake.level - LAKE_DEPTH * depth + shore);
+                if height < lake.level {
+                    water = Some(water.map_or(lake.level, |w| w.max(lake.level)));
+                }
+            }
+        }
+        (height, water)
+    }
+
+    /// Lowers a valley along the river line, with a flooded channel down
+    /// its middle
+    fn river(&self, x: i32, z: i32, terrain: f64) -> (f64, Option<f64>) {
+        let Some(noise) = &self.rivers else { return (terrain, None) };
+        if terrain <= self.sea_level {
+            return (terrain, None);
+        }
+        // Rivers narrow and vanish on high ground instead of climbing it
+        let top = self.river_max_height;
+        let fade = 1.0 - smoothstep(top - RIVER_FADE, top, terrain);
+        let width = self.river_width * fade;
+        let outer = width + self.river_bank_width * fade;
+        let line = noise.fbm(x as f64, z as f64).abs();
+        if line >= outer {
+            return (terrain, None);
+        }
+
+        let valley = smoothstep(outer, width, line);
+        let lowered = terrain - valley * self.river_depth;
+        if line >= width {
+            return (lowered, None);
+        }
+        let level = lowered.floor().max(self.sea_level);
+        let bed = level - 1.0 - CHANNEL_DEPTH * (1.0 - line / width);
+        (bed, Some(level))
+    }
+
+    /// The lake in a grid cell, if it has one
+    pub fn lake(&self, cell: (i32, i32), terrain_at: impl Fn(i32, i32) -> f64) -> Option<Lake> {
+        if self.lake_chance <= 0.0 {
+            return None;
+        }
+        if let Some(lake) = self.lakes.lock().unwrap().get(&cell) {
+            return *lake;
+        }
+        let lake = self.find_lake(cell, terrain_at);
+        let mut lakes = self.lakes.lock().unwrap();
+        if lakes.len() >= MAX_CACHED_LAKES {
+            lakes.clear();
+        }
+        lakes.insert(cell, lake);
+        lake
+    }
+
+    fn find_lake(&self, cell: (i32, i32), terrain_at: impl Fn(i32, i32) -> f64) -> Option<Lake> {
+        let mut rng = Rng::for_chunk(self.seed, cell, LAKE_SALT);
+        let radius = LAKE_MIN_RADIUS + rng.next_f64() * (self.lake_radius - LAKE_MIN_RADIUS);
+        let margin = (self.lake_radius + LAKE_SHORE).ceil() as i32 + 1;
+        let x = cell.0 * LAKE_CELL + rng.range(margin, LAKE_CELL - margin);
+        let z = cell.1 * LAKE_CELL + rng.range(margin, LAKE_CELL - margin);
+        if !rng.chance(self.lake_chance) {
+            return None;
+        }
+
+        // Water rises until it would spill over the lowest point of the rim
+        let center = terrain_at(x, z);
+        let rim: Vec<f64> = (0..RIM_SAMPLES)
+            .map(|i| {
+                let angle = i as f64 / RIM_SAMPLES as f64 * TAU;
+                let reach = radius + 2.0;
+                let (dx, dz) = (angle.cos() * reach, angle.sin() * reach);
+                terrain_at(x + dx.round() as i32, z + dz.round() as i32)
+            })
+            .collect();
+        let lowest = rim.iter().copied().fold(f64::INFINITY, f64::min);
+        let highest = rim.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let level = lowest.min(center).floor();
+        if highest - lowest > LAKE_MAX_SLOPE || level <= self.sea_level + 1.0 {
+            return None;
+        }
+        Some(Lake { x: x as f64, z: z as f64, radius, level })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_river_channel_is_flooded_below_its_banks() {
+        let hydrology = Hydrology::new(&GeneratorSettings { lakes: false, ..Default::default() });
+        let terrain = 24.0;
+        let mut channels = 0;
+        for x in -300..300 {
+            let (height, water) = hydrology.shape(x, 40, terrain, |_, _| terrain);
+            assert!(height <= terrain);
+            if let Some(level) = water {
+                channels += 1;
+                assert!(height < level && level < terrain);
+            }
+        }
+        assert!(channels > 0);
+    }
+
+    #[test]
+    fn test_lakes_fill_depressions() {
+        let settings = GeneratorSettings { rivers: false, lake_chance: 1.0, ..Default::default() };
+        let hydrology = Hydrology::new(&settings);
+        // A flat plain with a shallow dip in every cell
+        let terrain = |x: i32, z: i32| {
+            let (dx, dz) = (x.rem_euclid(LAKE_CELL) - 32, z.rem_euclid(LAKE_CELL) - 32);
+            30.0 - 2.0 * (-((dx * dx + dz * dz) as f64) / 400.0).exp()
+        };
+        let lake = hydrology.lake((2, -1), terrain).expect("no lake");
+        assert_eq!(hydrology.lake((2, -1), terrain), Some(lake));
+        assert!(lake.level <= 30.0);
+
+        let (x, z) = (lake.x as i32, lake.z as i32);
+        let (height, water) = hydrology.shape(x, z, terrain(x, z), terrain);
+        assert_eq!(water, Some(lake.level));
+        assert!(height <= lake.level - LAKE_DEPTH + 0.5);
+
+        // Steep ground never holds a lake
+        assert_eq!(hydrology.lake((5, 5), |x, _| x as f64), None);
+    }
+}
@@ -0,0 +1,614 @@
+//! Procedural world generation
+//!
+//! A [`WorldGenerator`] fills freshly created chunks. Generation only depends
+//! on the generator's settings and the chunk position, so chunks can be
+//! generated in any order, on any thread, and always come out the same.
+
+mod biome;
+mod caves;
+mod decoration;
+mod erosion;
+mod fractal;
+mod hydrology;
+mod ores;
+mod random;
+mod settings;
+mod structures;
+
+pub use biome::{Biome, BiomeParams, BiomeSource, ChunkBiomes, Climate};
+pub use caves::CaveCarver;
+pub use decoration::{Decorator, Tree, TREE_RADIUS};
+pub use erosion::{Eroder, ErosionRegion, REGION_MARGIN, REGION_SIZE};
+pub use fractal::{smoothstep, DomainWarp, FractalNoise};
+pub use hydrology::{Hydrology, Lake, LAKE_CELL};
+pub use ores::{place_ores, OreConfig};
+pub use random::{hash_position, Rng};
+pub use settings::{GeneratorSettings, SettingsError};
+pub use structures::{
+    PlacedStructure, PlacementRule, Rotation, StructureKind, StructurePlacer, StructureSite,
+    StructureTemplate, Transform,
+};
+
+use std::sync::Arc;
+
+use crate::block::BlockType;
+use crate::chunk::{Chunk, CHUNK_SIZE, MAX_Y, MIN_Y};
+use crate::world::World;
+
+/// Number of layers at the bottom of the world that may hold bedrock
+pub const BEDROCK_LAYERS: usize = 3;
+
+/// Fills chunks with generated terrain
+pub trait WorldGenerator: Send + Sync {
+    /// Generates the blocks of a chunk that is still empty
+    fn generate_chunk(&self, chunk: &mut Chunk);
+
+    /// Seed the generator was created with
+    fn seed(&self) -> u32;
+}
+
+/// Default heightmap terrain: a biome surface block, a subsurface layer,
+/// stone below
+///
+/// The surface combines rolling fBm hills with ridged mountain ranges, both
+/// scaled by the biome parameters blended around each column and sampled
+/// through a domain warp. Heights above `plateau_height` are compressed into
+/// plateaus.
+pub struct TerrainGenerator {
+    settings: GeneratorSettings,
+    biomes: BiomeSource,
+    hills: FractalNoise,
+    mountains: FractalNoise,
+    mountain_mask: FractalNoise,
+    warp: DomainWarp,
+    caves: CaveCarver,
+    decorator: Decorator,
+    eroder: Option<Eroder>,
+    hydrology: Hydrology,
+    structures: StructurePlacer,
+}
+
+/// Surface heights and water levels of a chunk's columns plus a one-column
+/// border
+pub struct Heightmap {
+    heights: [[usize; CHUNK_SIZE + 2]; CHUNK_SIZE + 2],
+    water: [[Option<usize>; CHUNK_SIZE + 2]; CHUNK_SIZE + 2],
+}
+
+impl Heightmap {
+    /// Height at local coordinates, from -1 to `CHUNK_SIZE` inclusive
+    pub fn get(&self, x: i32, z: i32) -> usize {
+        self.heights[(x + 1) as usize][(z + 1) as usize]
+    }
+
+    /// Water surface above a flooded column, at the same coordinates as `get`
+    pub fn water(&self, x: i32, z: i32) -> Option<usize> {
+        self.water[(x + 1) as usize][(z + 1) as usize]
+    }
+}
+
+impl TerrainGenerator {
+    /// Creates a generator from settings
+    pub fn new(settings: GeneratorSettings) -> Self {
+        let seed = settings.seed;
+        let mut hills = FractalNoise::new(seed, settings.octaves, settings.frequency);
+        hills.lacunarity = settings.lacunarity;
+        hills.persistence = settings.persistence;
+        Self {
+            biomes: BiomeSource::new(&settings),
+            hills,
+            mountains: FractalNoise::new(seed.wrapping_add(1), 5, settings.mountain_frequency),
+            mountain_mask: FractalNoise::new(seed.wrapping_add(2), 2, settings.mountain_mask_frequency),
+            warp: DomainWarp::new(seed.wrapping_add(3), settings.warp_frequency, settings.warp_strength),
+            caves: CaveCarver::new(&settings),
+            decorator: Decorator::new(&settings),
+            eroder: settings.erosion.then(|| Eroder::new(&settings)),
+            hydrology: Hydrology::new(&settings),
+            structures: StructurePlacer::new(&settings, StructurePlacer::default_kinds()),
+            settings,
+        }
+    }
+
+    pub fn settings(&self) -> &GeneratorSettings {
+        &self.settings
+    }
+
+    /// Climate noise deciding the biome of each column
+    pub fn biomes(&self) -> &BiomeSource {
+        &self.biomes
+    }
+
+    /// Surface height of the column at world coordinates (x, z)
+    pub fn surface_height(&self, x: i32, z: i32) -> usize {
+        self.height_with(x, z, self.biomes.blended_params(x, z))
+    }
+
+    /// Water surface above the column at (x, z), if the sea, a river or a
+    /// lake floods it
+    pub fn water_level(&self, x: i32, z: i32) -> Option<usize> {
+        self.column_with(x, z, self.biomes.blended_params(x, z)).1
+    }
+
+    /// The lake in a `LAKE_CELL` grid cell, if it has one
+    pub fn lake(&self, cell: (i32, i32)) -> Option<Lake> {
+        let terrain_at = |x, z| self.terrain_with(x, z, self.biomes.blended_params(x, z));
+        self.hydrology.lake(cell, terrain_at)
+    }
+
+    /// Erosion applied to a region, or `None` if erosion is disabled
+    pub fn eroded_region(&self, position: (i32, i32)) -> Option<Arc<ErosionRegion>> {
+        let eroder = self.eroder.as_ref()?;
+        Some(eroder.region(position, || self.region_terrain(position)))
+    }
+
+    /// Erosion cache files that could not be read since the last call; the
+    /// regions were simulated instead
+    pub fn take_erosion_errors(&self) -> Vec<((i32, i32), std::io::Error)> {
+        self.eroder.as_ref().map_or_else(Vec::new, Eroder::take_cache_errors)
+    }
+
+    /// Noise heights of an erosion region and its margin, before erosion
+    fn region_terrain(&self, position: (i32, i32)) -> Vec<f32> {
+        let side = (REGION_SIZE + 2 * REGION_MARGIN) as usize;
+        let size = CHUNK_SIZE as i32;
+        let (min_x, min_z) = ErosionRegion::origin(position);
+        let (min_x, min_z) = (min_x - REGION_MARGIN, min_z - REGION_MARGIN);
+        let mut heights = vec![0.0; side * side];
+        // Whole chunks cover the region and its margin, so biomes can be
+        // blended per chunk
+        let first = World::world_to_chunk(min_x, min_z).0;
+        for chunk_x in first.0..first.0 + side as i32 / size {
+            for chunk_z in first.1..first.1 + side as i32 / size {
+                let biomes = ChunkBiomes::new(&self.biomes, (chunk_x, chunk_z));
+                for x in 0..CHUNK_SIZE {
+                    for z in 0..CHUNK_SIZE {
+                        let (wx, wz) = World::chunk_to_world((chunk_x, chunk_z), x, z);
+                        let height = self.raw_height(wx as f64, wz as f64, biomes.params(x, z));
+                        let index = (wx - min_x) as usize * side + (wz - min_z) as usize;
+                        heights[index] = height as f32;
+                    }
+                }
+            }
+        }
+        heights
+    }
+
+    /// Surface heights for a chunk and the columns bordering it
+    pub fn heightmap(&self, position: (i32, i32), biomes: &ChunkBiomes) -> Heightmap {
+        let mut heightmap = Heightmap {
+            heights: [[0; CHUNK_SIZE + 2]; CHUNK_SIZE + 2],
+            water: [[None; CHUNK_SIZE + 2]; CHUNK_SIZE + 2],
+        };
+        let (min_x, min_z) = World::chunk_to_world(position, 0, 0);
+        for i in 0..CHUNK_SIZE + 2 {
+            for j in 0..CHUNK_SIZE + 2 {
+                let (x, z) = (min_x + i as i32 - 1, min_z + j as i32 - 1);
+                let (height, water) = self.column_with(x, z, biomes.params_at(x, z));
+                heightmap.heights[i][j] = height;
+                heightmap.water[i][j] = water;
+            }
+        }
+        heightmap
+    }
+
+    /// Surface and subsurface blocks of a column, with beaches where the
+    /// land meets the sea
+    fn column_blocks(
+        &self,
+        biome: Biome,
+        height: usize,
+        flooded: bool,
+    ) -> (BlockType, BlockType) {
+        let sea_level = self.settings.sea_level;
+        let height = height as i32;
+        let shore = height >= sea_level - 3 && height <= sea_level + self.settings.beach_height;
+        if shore && biome != Biome::Mountains {
+            return (BlockType::Sand, BlockType::Sand);
+        }
+        let surface = biome.surface_block();
+        if flooded && surface == BlockType::Grass {
+            // Grass does not grow under water
+            return (BlockType::Dirt, BlockType::Dirt);
+        }
+        (surface, biome.subsurface_block())
+    }
+
+    /// Fills the open air between each flooded column's surface and its
+    /// water level with water
+    ///
+    /// Caves stay dry: they are sealed off below the sea, river and lake
+    /// beds, so only air directly above the surface is flooded.
+    fn fill_water(&self, chunk: &mut Chunk, heights: &Heightmap) {
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                let (x_i, z_i) = (x as i32, z as i32);
+                let Some(level) = heights.water(x_i, z_i) else { continue };
+                for y in heights.get(x_i, z_i) + 1..=level {
+                    if chunk.get_block(x, y, z) == Some(BlockType::Air) {
+                        chunk.set_block(x, y, z, BlockType::Water);
+                    }
+                }
+            }
+        }
+    }
+
+    fn height_with(&self, x: i32, z: i32, params: BiomeParams) -> usize {
+        self.column_with(x, z, params).0
+    }
+
+    /// Surface height of a column and the water level above it, if flooded
+    fn column_with(&self, x: i32, z: i32, params: BiomeParams) -> (usize, Option<usize>) {
+        let terrain = self.terrain_with(x, z, params);
+        let (height, water) = self.hydrology.shape(x, z, terrain, |x, z| {
+            self.terrain_with(x, z, self.biomes.blended_params(x, z))
+        });
+        // The surface bottoms out at y = 0; the deep stone below it belongs to
+        // caves, ores and dungeons
+        let height = (height.round().max(0.0) as usize).min(MAX_Y as usize - 1);
+
+        let sea = self.settings.sea_level.min(MAX_Y - 1);
+        let level = water
+            .map(|level| (level.round().max(0.0) as usize).min(MAX_Y as usize - 1))
+            .max((sea >= 0).then_some(sea as usize));
+        (height, level.filter(|&level| level > height))
+    }
+
+    /// Terrain height after erosion, before rivers and lakes
+    fn terrain_with(&self, x: i32, z: i32, params: BiomeParams) -> f64 {
+        let mut height = self.raw_height(x as f64, z as f64, params);
+        if let Some(region) = self.eroded_region(ErosionRegion::containing(x, z)) {
+            height += region.delta(x, z);
+        }
+        height
+    }
+
+    /// Unclamped terrain height before rounding to whole blocks
+    fn raw_height(&self, x: f64, z: f64, params: BiomeParams) -> f64 {
+        let s = &self.settings;
+        let (wx, wz) = self.warp.warp(x, z);
+
+        let hills = s.base_height
+            + params.height_offset
+            + self.hills.fbm(wx, wz) * s.amplitude * params.hilliness;
+
+        // The mask breaks mountain biomes into separate ranges and lets
+        // lower biomes grow the odd lone peak
+        let mask = 0.25 + 0.75 * smoothstep(-0.1, 0.4, self.mountain_mask.fbm(x, z));
+        let ridge = self.mountains.ridged(wx, wz);
+        let height = hills + ridge * ridge * s.mountain_height * params.mountains * mask;
+
+        if height > s.plateau_height {
+            s.plateau_height + (height - s.plateau_height) * s.plateau_falloff
+        } else {
+            height
+        }
+    }
+}
+
+impl Default for TerrainGenerator {
+    fn default() -> Self {
+        Self::new(GeneratorSettings::default())
+    }
+}
+
+impl WorldGenerator for TerrainGenerator {
+    fn generate_chunk(&self, chunk: &mut Chunk) {
+        let dirt_depth = self.settings.dirt_depth as i32;
+        let biomes = ChunkBiomes::new(&self.biomes, chunk.position);
+        let heights = self.heightmap(chunk.position, &biomes);
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                let (world_x, world_z) = World::chunk_to_world(chunk.position, x, z);
+                let height = heights.get(x as i32, z as i32);
+                let flooded = heights.water(x as i32, z as i32).is_some();
+                let (surface, subsurface) = self.column_blocks(biomes.biome(x, z), height, flooded);
+
+                let height = height as i32;
+                for y in MIN_Y..=height {
+                    let block = if is_bedrock(self.settings.seed, world_x, y, world_z) {
+                        BlockType::Bedrock
+                    } else if y == height {
+                        surface
+                    } else if y + dirt_depth >= height {
+                        subsurface
+                    } else {
+                        BlockType::Stone
+                    };
+                    chunk.set_block_at(x, y, z, block);
+                }
+            }
+        }
+
+        if self.settings.caves {
+            self.caves.carve(chunk, &heights);
+        }
+        place_ores(chunk, self.settings.seed, &self.settings.ores);
+        self.fill_water(chunk, &heights);
+
+        let (min_x, min_z) = World::chunk_to_world(chunk.position, 0, 0);
+        let margin = -1..=CHUNK_SIZE as i32;
+        self.decorator.decorate(chunk, |x, z| {
+            let (local_x, local_z) = (x - min_x, z - min_z);
+            let (height, water) = if margin.contains(&local_x) && margin.contains(&local_z) {
+                (heights.get(local_x, local_z), heights.water(local_x, local_z))
+            } else {
+                self.column_with(x, z, biomes.params_at(x, z))
+            };
+            let biome = self.biomes.biome_at(x, z);
+            let (surface, _) = self.column_blocks(biome, height, water.is_some());
+            let fertile = matches!(surface, BlockType::Grass | BlockType::Snow);
+            let dry = water.is_none() && height as i32 > self.settings.sea_level;
+            (fertile && dry).then_some((biome, height))
+        });
+
+        if self.settings.structures {
+            self.structures.place(chunk, self);
+        }
+    }
+
+    fn seed(&self) -> u32 {
+        self.settings.seed
+    }
+}
+
+impl StructureSite for TerrainGenerator {
+    fn biome(&self, x: i32, z: i32) -> Biome {
+        self.biomes.biome_at(x, z)
+    }
+
+    fn surface_height(&self, x: i32, z: i32) -> usize {
+        TerrainGenerator::surface_height(self, x, z)
+    }
+
+    fn is_flooded(&self, x: i32, z: i32) -> bool {
+        self.water_level(x, z).is_some()
+    }
+}
+
+/// The bottom layer at `MIN_Y` is solid bedrock, thinning out over the
+/// layers above it
+fn is_bedrock(seed: u32, x: i32, y: i32, z: i32) -> bool {
+    let depth = (y - MIN_Y) as u64;
+    let roll = hash_position(seed, x, y, z) % BEDROCK_LAYERS as u64;
+    depth == 0 || (depth < BEDROCK_LAYERS as u64 && roll >= depth)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::{SECTION_COUNT, SECTION_SIZE};
+
+    fn generate(generator: &TerrainGenerator, position: (i32, i32)) -> Chunk {
+        let mut chunk = Chunk::new(position);
+        generator.generate_chunk(&mut chunk);
+        chunk
+    }
+
+    #[test]
+    fn test_same_seed_same_chunk() {
+        let a = TerrainGenerator::new(GeneratorSettings { seed: 5, ..Default::default() });
+        let b = TerrainGenerator::new(GeneratorSettings { seed: 5, ..Default::default() });
+        assert!(generate(&a, (-3, 8)) == generate(&b, (-3, 8)));
+
+        let c = TerrainGenerator::new(GeneratorSettings { seed: 6, ..Default::default() });
+        assert!(generate(&a, (-3, 8)) != generate(&c, (-3, 8)));
+    }
+
+    #[test]
+    fn test_layers_follow_settings() {
+        let generator = TerrainGenerator::new(GeneratorSettings {
+            dirt_depth: 4,
+            caves: false,
+            ores: Vec::new(),
+            ..Default::default()
+        });
+        let chunk = generate(&generator, (0, 0));
+        let height = generator.surface_height(3, 5);
+        assert_eq!(chunk.get_block(3, height, 5), Some(BlockType::Grass));
+        assert_eq!(chunk.get_block(3, height + 1, 5), Some(BlockType::Air));
+        for depth in 1..=4 {
+            assert_eq!(chunk.get_block(3, height - depth, 5), Some(BlockType::Dirt));
+        }
+        assert_eq!(chunk.get_block(3, height - 5, 5), Some(BlockType::Stone));
+
+        // Stone reaches down below y = 0 to the bedrock floor at MIN_Y
+        assert_eq!(chunk.get_block_at(3, MIN_Y, 5), Some(BlockType::Bedrock));
+        for y in MIN_Y + BEDROCK_LAYERS as i32..0 {
+            assert_eq!(chunk.get_block_at(3, y, 5), Some(BlockType::Stone));
+        }
+    }
+
+    #[test]
+    fn test_sea_fills_open_water_only() {
+        let generator = TerrainGenerator::default();
+        let sea_level = generator.settings().sea_level as usize;
+        let mut underwater = 0;
+        let mut beaches = 0;
+        for chunk_x in (-60..60).step_by(6) {
+            let position = (chunk_x, 2);
+            let chunk = generate(&generator, position);
+            let biomes = ChunkBiomes::new(generator.biomes(), position);
+            let heights = generator.heightmap(position, &biomes);
+            for x in 0..CHUNK_SIZE {
+                for z in 0..CHUNK_SIZE {
+                    let height = heights.get(x as i32, z as i32);
+                    let level = heights.water(x as i32, z as i32);
+                    if height < sea_level {
+                        // Rivers may run into the sea slightly above it
+                        assert!(level.is_some_and(|level| level >= sea_level));
+                    }
+                    for y in 0..MAX_Y as usize {
+                        let block = chunk.get_block(x, y, z).unwrap();
+                        let open_water = level.is_some_and(|level| y > height && y <= level);
+                        // No air pockets under water, no water in caves or above the water level
+                        let at = (position, x, y, z);
+                        assert_eq!(block == BlockType::Water, open_water, "at {:?}", at);
+                        // Every water block rests on water or ground
+                        if block == BlockType::Water {
+                            let below = chunk.get_block(x, y - 1, z).unwrap();
+                            assert!(below == BlockType::Water || below.is_solid());
+                        }
+                    }
+                    if height < sea_level {
+                        underwater += 1;
+                    }
+                    let sand = chunk.get_block(x, height, z) == Some(BlockType::Sand);
+                    if sand && height.abs_diff(sea_level) <= 1 {
+                        beaches += 1;
+                    }
+                }
+            }
+        }
+        assert!(underwater > 0, "no sea found");
+        assert!(beaches > 0, "no beaches found");
+    }
+
+    #[test]
+    fn test_rivers_and_lakes_continue_across_chunk_borders() {
+        let generator = TerrainGenerator::default();
+        let sea_level = generator.settings().sea_level as usize;
+        let mut inland = 0;
+        for chunk_z in -8..8 {
+            let mut previous: Option<Heightmap> = None;
+            for chunk_x in -8..8 {
+                let position = (chunk_x, chunk_z);
+                let biomes = ChunkBiomes::new(generator.biomes(), position);
+                let heights = generator.heightmap(position, &biomes);
+                for x in 0..CHUNK_SIZE as i32 {
+                    for z in 0..CHUNK_SIZE as i32 {
+                        if heights.water(x, z).is_some_and(|level| level > sea_level) {
+                            inland += 1;
+                        }
+                    }
+                }
+                // The border columns seen by one chunk match its neighbour's own columns
+                if let Some(previous) = previous {
+                    for z in 0..CHUNK_SIZE as i32 {
+                        assert_eq!(previous.get(CHUNK_SIZE as i32, z), heights.get(0, z));
+                        assert_eq!(previous.water(CHUNK_SIZE as i32, z), heights.water(0, z));
+                        assert_eq!(previous.water(CHUNK_SIZE as i32 - 1, z), heights.water(-1, z));
+                    }
+                }
+                previous = Some(heights);
+            }
+        }
+        assert!(inland > 0, "no rivers or lakes found");
+
+        let lake = (-8..8)
+            .flat_map(|x| (-8..8).map(move |z| (x, z)))
+            .find_map(|cell| generator.lake(cell))
+            .expect("no lake found");
+        let (x, z) = (lake.x as i32, lake.z as i32);
+        assert_eq!(generator.water_level(x, z), Some(lake.level as usize));
+        assert!(generator.surface_height(x, z) < lake.level as usize);
+    }
+
+    #[test]
+    fn test_heights_without_mountains_stay_within_amplitude() {
+        let settings = GeneratorSettings {
+            mountain_height: 0.0,
+            rivers: false,
+            lakes: false,
+            ..Default::default()
+        };
+        let generator = TerrainGenerator::new(settings.clone());
+        for x in (-200..200).step_by(7) {
+            for z in (-200..200).step_by(7) {
+                let height = generator.surface_height(x, z) as f64;
+                let params = generator.biomes().blended_params(x, z);
+                let expected = settings.base_height + params.height_offset;
+                assert!((height - expected).abs() <= settings.amplitude * params.hilliness + 0.5);
+            }
+        }
+    }
+
+    #[test]
+    fn test_terrain_has_mountains_and_valleys() {
+        let settings = GeneratorSettings::default();
+        let generator = TerrainGenerator::new(settings.clone());
+        let heights: Vec<usize> = (-60..60)
+            .flat_map(|x| (-60..60).map(move |z| (x * 24, z * 24)))
+            .map(|(x, z)| generator.surface_height(x, z))
+            .collect();
+        let max = *heights.iter().max().unwrap() as f64;
+        let min = *heights.iter().min().unwrap() as f64;
+        assert!(max > settings.base_height + settings.amplitude + 5.0, "no mountains, max {}", max);
+        assert!(min < settings.base_height - settings.amplitude / 2.0, "no valleys, min {}", min);
+        // Plateau compression keeps peaks well below the chunk ceiling
+        assert!(max < MAX_Y as f64 - 1.0);
+    }
+
+    #[test]
+    fn test_erosion_reshapes_regions_without_seams() {
+        let settings = GeneratorSettings {
+            erosion: true,
+            erosion_droplets: 6000,
+            ..Default::default()
+        };
+        let eroded = TerrainGenerator::new(settings.clone());
+        let plain = TerrainGenerator::default();
+        let mut changed = 0;
+        for x in (0..REGION_SIZE).step_by(3) {
+            for z in (0..REGION_SIZE).step_by(3) {
+                if eroded.surface_height(x, z) != plain.surface_height(x, z) {
+                    changed += 1;
+                }
+            }
+        }
+        assert!(changed > 100, "only {} columns changed", changed);
+
+        // Region edges keep the noise height, so neighbouring regions line up
+        for i in 0..REGION_SIZE {
+            for (x, z) in [(i, 0), (0, i), (i, REGION_SIZE - 1), (REGION_SIZE - 1, i)] {
+                assert_eq!(eroded.surface_height(x, z), plain.surface_height(x, z));
+            }
+        }
+
+        let again = TerrainGenerator::new(settings);
+        assert_eq!(again.eroded_region((0, 0)), eroded.eroded_region((0, 0)));
+    }
+
+    /// FNV-1a over every block, stable across platforms and Rust versions
+    fn chunk_hash(chunk: &Chunk) -> u64 {
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        for x in 0..CHUNK_SIZE {
+            for y in MIN_Y..MAX_Y {
+                for z in 0..CHUNK_SIZE {
+                    hash ^= chunk.get_block_at(x, y, z).unwrap() as u64;
+                    hash = hash.wrapping_mul(0x0100_0000_01b3);
+                }
+            }
+        }
+        hash
+    }
+
+    #[test]
+    fn test_generated_sections_pack_smaller_than_dense() {
+        let generator = TerrainGenerator::default();
+        let chunk = generate(&generator, (3, -2));
+        let sections: Vec<_> =
+            (0..SECTION_COUNT).filter_map(|index| chunk.section(index)).collect();
+        assert!(!sections.is_empty());
+        // A dense section takes a byte per block
+        let dense = sections.len() * SECTION_SIZE * SECTION_SIZE * SECTION_SIZE;
+        let paletted: usize = sections.iter().map(|section| section.memory_usage()).sum();
+        assert!(paletted < dense * 3 / 4, "{} of {} bytes", paletted, dense);
+    }
+
+    /// Catches accidental changes to generated terrain. If a change is
+    /// intentional, update the expected hashes.
+    #[test]
+    fn test_generation_snapshots() {
+        let cases = [
+            (42, (0, 0), 0x666cd626868580c3),
+            (42, (-7, 3), 0xd0028373b51fd79f),
+            (42, (6, 2), 0x377bdfffcca6f79d),
+            (1337, (12, -40), 0xd87ce0746594dfda),
+        ];
+        for (seed, position, expected) in cases {
+            let generator = TerrainGenerator::new(GeneratorSettings { seed, ..Default::default() });
+            let hash = chunk_hash(&generate(&generator, position));
+            assert_eq!(hash, expected, "seed {} chunk {:?}: {:#018x}", seed, position, hash);
+        }
+    }
+}
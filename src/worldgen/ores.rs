@@ -0,0 +1,166 @@
+use serde::{Deserialize, Serialize};
+
+use crate::block::BlockType;
+use crate::chunk::{Chunk, CHUNK_SIZE, MAX_Y, MIN_Y};
+
+use super::random::Rng;
+
+/// Salt separating the ore random streams from other stages
+const ORE_SALT: u64 = 0x6f72_6573;
+
+/// Where and how often one kind of ore is placed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OreConfig {
+    /// Block placed in the veins
+    pub block: BlockType,
+    /// Lowest layer a vein may start in, clamped to `MIN_Y`
+    pub min_height: i32,
+    /// Highest layer a vein may start in, clamped below `MAX_Y`
+    pub max_height: i32,
+    /// Number of blocks a vein tries to place
+    pub vein_size: u32,
+    /// Average number of veins per chunk; fractions are rolled per chunk
+    pub veins_per_chunk: f64,
+}
+
+impl OreConfig {
+    /// Coal, iron, gold and diamond, from common and shallow to rare and deep
+    pub fn defaults() -> Vec<OreConfig> {
+        let ore = |block, min_height, max_height, vein_size, veins_per_chunk| OreConfig {
+            block,
+            min_height,
+            max_height,
+            vein_size,
+            veins_per_chunk,
+        };
+        vec![
+            ore(BlockType::CoalOre, -8, 48, 12, 14.0),
+            ore(BlockType::IronOre, -32, 40, 8, 8.0),
+            ore(BlockType::GoldOre, -56, 24, 6, 2.0),
+            ore(BlockType::DiamondOre, -60, -24, 4, 0.8),
+        ]
+    }
+}
+
+/// Scatters ore veins through the stone of a chunk
+///
+/// Each ore draws from its own random stream seeded by the chunk position,
+/// so regenerating a chunk gives identical veins and editing one ore's
+/// settings leaves the others in place. Veins stay inside the chunk and
+/// only replace stone.
+pub fn place_ores(chunk: &mut Chunk, seed: u32, ores: &[OreConfig]) {
+    for (index, ore) in ores.iter().enumerate() {
+        let min_height = ore.min_height.max(MIN_Y);
+        let max_height = ore.max_height.min(MAX_Y - 1);
+        if min_height > max_height || ore.vein_size == 0 {
+            continue;
+        }
+
+        let mut rng = Rng::for_chunk(seed, chunk.position, ORE_SALT + index as u64);
+        let mut veins = ore.veins_per_chunk.max(0.0).floor() as u32;
+        if rng.chance(ore.veins_per_chunk.fract()) {
+            veins += 1;
+        }
+
+        for _ in 0..veins {
+            let mut position = [
+                rng.range(0, CHUNK_SIZE as i32),
+                rng.range(min_height, max_height + 1),
+                rng.range(0, CHUNK_SIZE as i32),
+            ];
+            for _ in 0..ore.vein_size {
+                let [x, y, z] = position;
+                let (x, z) = (x as usize, z as usize);
+                if chunk.get_block_at(x, y, z) == Some(BlockType::Stone) {
+                    chunk.set_block_at(x, y, z, ore.block);
+                }
+
+                // Wander one block along a random axis, staying in the chunk
+                // and inside the ore's height range
+                let axis = rng.range(0, 3) as usize;
+                let step = if rng.chance(0.5) { 1 } else { -1 };
+                let (low, high) = match axis {
+                    1 => (min_height, max_height),
+                    _ => (0, CHUNK_SIZE as i32 - 1),
+                };
+                position[axis] = (position[axis] + step).clamp(low, high);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stone_chunk(position: (i32, i32)) -> Chunk {
+        let mut chunk = Chunk::new(position);
+        for x in 0..CHUNK_SIZE {
+            for y in MIN_Y..MAX_Y {
+                for z in 0..CHUNK_SIZE {
+                    chunk.set_block_at(x, y, z, BlockType::Stone);
+                }
+            }
+        }
+        chunk
+    }
+
+    fn count(chunk: &Chunk, block: BlockType) -> usize {
+        (0..CHUNK_SIZE)
+            .flat_map(|x| (MIN_Y..MAX_Y).map(move |y| (x, y)))
+            .flat_map(|(x, y)| (0..CHUNK_SIZE).map(move |z| (x, y, z)))
+            .filter(|&(x, y, z)| chunk.get_block_at(x, y, z) == Some(block))
+            .count()
+    }
+
+    #[test]
+    fn test_ores_are_deterministic_per_chunk() {
+        let ores = OreConfig::defaults();
+        let mut a = stone_chunk((4, -9));
+        let mut b = stone_chunk((4, -9));
+        place_ores(&mut a, 42, &ores);
+        place_ores(&mut b, 42, &ores);
+        assert!(a == b);
+
+        let mut other = stone_chunk((5, -9));
+        place_ores(&mut other, 42, &ores);
+        assert!(a != other);
+    }
+
+    #[test]
+    fn test_ores_respect_height_ranges_and_rarity() {
+        let ores = OreConfig::defaults();
+        let mut totals = vec![0; ores.len()];
+        let mut deep = vec![0; ores.len()];
+        for position in [(0, 0), (1, 0), (-3, 2), (7, 7), (-10, -4), (20, 1), (2, 30), (-6, -6)] {
+            let mut chunk = stone_chunk(position);
+            place_ores(&mut chunk, 9, &ores);
+            for (index, ore) in ores.iter().enumerate() {
+                totals[index] += count(&chunk, ore.block);
+                for x in 0..CHUNK_SIZE {
+                    for z in 0..CHUNK_SIZE {
+                        for y in MIN_Y..MAX_Y {
+                            let found = chunk.get_block_at(x, y, z) == Some(ore.block);
+                            assert!(!found || (ore.min_height..=ore.max_height).contains(&y));
+                            if found && y < 0 {
+                                deep[index] += 1;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        assert!(totals.windows(2).all(|pair| pair[0] > pair[1]), "{:?}", totals);
+        assert!(totals[totals.len() - 1] > 0);
+        // Every default ore reaches into the deep stone below y = 0
+        assert!(deep.iter().all(|&count| count > 0), "{:?}", deep);
+    }
+
+    #[test]
+    fn test_ores_only_replace_stone() {
+        let mut chunk = Chunk::new((0, 0));
+        place_ores(&mut chunk, 42, &OreConfig::defaults());
+        let height = (MAX_Y - MIN_Y) as usize;
+        assert_eq!(count(&chunk, BlockType::Air), CHUNK_SIZE * height * CHUNK_SIZE);
+    }
+}
@@ -0,0 +1,266 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use super::OreConfig;
+
+/// Errors from loading or saving generator settings
+#[derive(Debug)]
+pub enum SettingsError {
+    Io(std::io::Error),
+    Parse(toml::de::Error),
+    Serialize(toml::ser::Error),
+}
+
+impl std::fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SettingsError::Io(e) => write!(f, "I/O error: {}", e),
+            SettingsError::Parse(e) => write!(f, "invalid generator settings: {}", e),
+            SettingsError::Serialize(e) => write!(f, "failed to serialize generator settings: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for SettingsError {}
+
+impl From<std::io::Error> for SettingsError {
+    fn from(e: std::io::Error) -> Self {
+        SettingsError::Io(e)
+    }
+}
+
+/// Tunable parameters for [`TerrainGenerator`](super::TerrainGenerator)
+///
+/// Missing fields fall back to their defaults, so a settings file only needs
+/// to list the values it changes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GeneratorSettings {
+    /// Seed for every noise source; the same seed always yields the same world
+    pub seed: u32,
+    /// Number of noise layers summed for the heightmap
+    pub octaves: u32,
+    /// Horizontal frequency of the first octave, in cycles per block
+    pub frequency: f64,
+    /// Frequency multiplier between octaves
+    pub lacunarity: f64,
+    /// Amplitude multiplier between octaves
+    pub persistence: f64,
+    /// Maximum deviation of the surface from `base_height`, in blocks
+    pub amplitude: f64,
+    /// Average surface height in blocks
+    pub base_height: f64,
+    /// Maximum distance in blocks that domain warping moves sample points
+    pub warp_strength: f64,
+    /// Frequency of the domain warp noise
+    pub warp_frequency: f64,
+    /// Frequency of the ridged noise forming mountain ranges
+    pub mountain_frequency: f64,
+    /// Height of the tallest mountain crests above the base terrain
+    pub mountain_height: f64,
+    /// Frequency of the mask deciding which regions get mountains
+    pub mountain_mask_frequency: f64,
+    /// Height above which terrain is flattened into plateaus
+    pub plateau_height: f64,
+    /// How much of the height above `plateau_height` is kept (0 = flat tops)
+    pub plateau_falloff: f64,
+    /// Frequency of the temperature and humidity noise choosing biomes
+    pub biome_frequency: f64,
+    /// Frequency of the noise separating oceans, lowlands and highlands
+    pub continent_frequency: f64,
+    /// Whether to erode the terrain with simulated rain and landslides
+    pub erosion: bool,
+    /// Water droplets simulated per 128x128 erosion region
+    pub erosion_droplets: u32,
+    /// Passes of thermal erosion smoothing slopes after the rain
+    pub erosion_thermal_passes: u32,
+    /// Steepest height difference between neighbouring columns that thermal
+    /// erosion leaves alone
+    pub erosion_talus: f64,
+    /// Directory of precomputed erosion regions, see the `erode_regions` example
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub erosion_cache: Option<String>,
+    /// Whether to carve caves into the terrain
+    pub caves: bool,
+    /// Frequency of the 3D noise forming large caverns
+    pub cave_frequency: f64,
+    /// Noise value above which caverns are carved; higher means fewer
+    pub cave_threshold: f64,
+    /// Frequency of the noise forming long tunnels
+    pub tunnel_frequency: f64,
+    /// Thickness of noise tunnels; 0 disables them
+    pub tunnel_width: f64,
+    /// Chance of a worm carver starting in each chunk
+    pub worm_chance: f64,
+    /// Number of one-block steps a worm carver walks
+    pub worm_length: u32,
+    /// Solid layers kept between caves and the beds of the sea, rivers and lakes
+    pub cave_ocean_cover: u32,
+    /// Whether to carve rivers along the zero lines of a noise field
+    pub rivers: bool,
+    /// Frequency of the river noise; lower values space rivers further apart
+    pub river_frequency: f64,
+    /// Width of river channels, in river noise units
+    pub river_width: f64,
+    /// Width of the sloped banks on each side of a channel, in noise units
+    pub river_bank_width: f64,
+    /// How far rivers lower the terrain below their banks, in blocks
+    pub river_depth: f64,
+    /// Height above which rivers thin out and stop
+    pub river_max_height: f64,
+    /// Whether to fill dips in flat ground with lakes
+    pub lakes: bool,
+    /// Chance of each 64x64 block cell trying to hold a lake
+    pub lake_chance: f64,
+    /// Largest lake radius in blocks
+    pub lake_radius: f64,
+    /// Height of the sea surface in blocks; open air at or below it is
+    /// filled with water
+    pub sea_level: i32,
+    /// Columns up to this far above sea level get a sand beach
+    pub beach_height: i32,
+    /// Depth of the dirt layer below the grass surface block
+    pub dirt_depth: u32,
+    /// Multiplier for the number of trees each biome grows; 0 disables trees
+    pub tree_density: f64,
+    /// Multiplier for tall grass and flowers; 0 disables them
+    pub plant_density: f64,
+    /// Whether to place huts, ruins and dungeons
+    pub structures: bool,
+    /// Ore veins scattered through the stone, as `[[ores]]` tables
+    pub ores: Vec<OreConfig>,
+}
+
+impl Default for GeneratorSettings {
+    fn default() -> Self {
+        Self {
+            seed: 42,
+            octaves: 4,
+            frequency: 0.01,
+            lacunarity: 2.0,
+            persistence: 0.5,
+            amplitude: 10.0,
+            base_height: 20.0,
+            warp_strength: 24.0,
+            warp_frequency: 0.004,
+            mountain_frequency: 0.006,
+            mountain_height: 30.0,
+            mountain_mask_frequency: 0.0025,
+            plateau_height: 44.0,
+            plateau_falloff: 0.3,
+            biome_frequency: 0.0015,
+            continent_frequency: 0.001,
+            erosion: false,
+            erosion_droplets: 40_000,
+            erosion_thermal_passes: 10,
+            erosion_talus: 1.5,
+            erosion_cache: None,
+            caves: true,
+            cave_frequency: 0.03,
+            cave_threshold: 0.55,
+            tunnel_frequency: 0.02,
+            tunnel_width: 0.06,
+            worm_chance: 0.35,
+            worm_length: 90,
+            cave_ocean_cover: 4,
+            rivers: true,
+            river_frequency: 0.002,
+            river_width: 0.025,
+            river_bank_width: 0.05,
+            river_depth: 3.0,
+            river_max_height: 42.0,
+            lakes: true,
+            lake_chance: 0.35,
+            lake_radius: 12.0,
+            sea_level: 16,
+            beach_height: 1,
+            dirt_depth: 2,
+            tree_density: 1.0,
+            plant_density: 1.0,
+            structures: true,
+            ores: OreConfig::defaults(),
+        }
+    }
+}
+
+impl GeneratorSettings {
+    /// Parses settings from TOML text
+    pub fn from_toml(text: &str) -> Result<Self, SettingsError> {
+        toml::from_str(text).map_err(SettingsError::Parse)
+    }
+
+    /// Serializes settings to TOML text
+    pub fn to_toml(&self) -> Result<String, SettingsError> {
+        toml::to_string_pretty(self).map_err(SettingsError::Serialize)
+    }
+
+    /// Loads settings from a TOML file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SettingsError> {
+        Self::from_toml(&std::fs::read_to_string(path)?)
+    }
+
+    /// Writes settings to a TOML file
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SettingsError> {
+        std::fs::write(path, self.to_toml()?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::BlockType;
+
+    #[test]
+    fn test_toml_round_trip() {
+        let settings = GeneratorSettings {
+            seed: 7,
+            octaves: 6,
+            sea_level: 12,
+            ..Default::default()
+        };
+        let text = settings.to_toml().unwrap();
+        assert_eq!(GeneratorSettings::from_toml(&text).unwrap(), settings);
+    }
+
+    #[test]
+    fn test_partial_toml_uses_defaults() {
+        let settings = GeneratorSettings::from_toml("seed = 99\nfrequency = 0.02\n").unwrap();
+        assert_eq!(settings.seed, 99);
+        assert_eq!(settings.frequency, 0.02);
+        assert_eq!(settings.octaves, GeneratorSettings::default().octaves);
+    }
+
+    #[test]
+    fn test_ores_from_toml() {
+        let text = r#"
+            [[ores]]
+            block = "GoldOre"
+            min_height = -20
+            max_height = 10
+            vein_size = 5
+            veins_per_chunk = 1.5
+        "#;
+        let settings = GeneratorSettings::from_toml(text).unwrap();
+        assert_eq!(
+            settings.ores,
+            vec![OreConfig {
+                block: BlockType::GoldOre,
+                min_height: -20,
+                max_height: 10,
+                vein_size: 5,
+                veins_per_chunk: 1.5,
+            }]
+        );
+        assert_eq!(GeneratorSettings::from_toml("").unwrap().ores, OreConfig::defaults());
+    }
+
+    #[test]
+    fn test_invalid_toml_is_rejected() {
+        assert!(matches!(
+            GeneratorSettings::from_toml("seed = \"not a number\""),
+            Err(SettingsError::Parse(_))
+        ));
+    }
+}
@@ -0,0 +1,519 @@
+use std::collections::BTreeMap;
+
+use crate::block::BlockType;
+use crate::chunk::{Chunk, CHUNK_SIZE, MAX_Y, MIN_Y};
+use crate::world::World;
+
+use super::random::Rng;
+use super::{Biome, GeneratorSettings, BEDROCK_LAYERS};
+
+/// Salt separating structure random streams from other stages
+const STRUCTURE_SALT: u64 = 0x7374_7275;
+/// Deepest a foundation reaches down to solid ground
+const MAX_FOUNDATION: usize = 8;
+
+/// Quarter turns around the vertical axis, clockwise seen from above
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    None,
+    Clockwise90,
+    Clockwise180,
+    Clockwise270,
+}
+
+impl Rotation {
+    pub const ALL: [Rotation; 4] = [
+        Rotation::None,
+        Rotation::Clockwise90,
+        Rotation::Clockwise180,
+        Rotation::Clockwise270,
+    ];
+}
+
+/// Orientation a template is stamped with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Transform {
+    pub rotation: Rotation,
+    /// Mirror along the X axis before rotating
+    pub mirror: bool,
+}
+
+impl Transform {
+    pub const IDENTITY: Transform = Transform { rotation: Rotation::None, mirror: false };
+
+    /// Maps an offset from the template origin to an offset in the world
+    pub fn apply(&self, [x, y, z]: [i32; 3]) -> [i32; 3] {
+        let x = if self.mirror { -x } else { x };
+        let (x, z) = match self.rotation {
+            Rotation::None => (x, z),
+            Rotation::Clockwise90 => (-z, x),
+            Rotation::Clockwise180 => (-x, -z),
+            Rotation::Clockwise270 => (z, -x),
+        };
+        [x, y, z]
+    }
+}
+
+/// A handcrafted structure stored as a sparse block volume
+///
+/// Offsets are relative to the origin, the point placed on the anchor when
+/// the structure is stamped. Air entries clear terrain, and positions that
+/// are not listed keep whatever was generated there.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructureTemplate {
+    pub name: String,
+    blocks: BTreeMap<[i32; 3], BlockType>,
+    /// Block filling the gap between the lowest layer and the ground
+    pub foundation: Option<BlockType>,
+}
+
+impl StructureTemplate {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            blocks: BTreeMap::new(),
+            foundation: None,
+        }
+    }
+
+    /// Sets one block, replacing any earlier block at the same offset
+    pub fn set(&mut self, offset: [i32; 3], block: BlockType) -> &mut Self {
+        self.blocks.insert(offset, block);
+        self
+    }
+
+    /// Sets every block in the box between two corners, inclusive
+    pub fn fill(&mut self, min: [i32; 3], max: [i32; 3], block: BlockType) -> &mut Self {
+        for x in min[0]..=max[0] {
+            for y in min[1]..=max[1] {
+                for z in min[2]..=max[2] {
+                    self.set([x, y, z], block);
+                }
+            }
+        }
+        self
+    }
+
+    /// Sets the outer shell of a box, inclusive, clearing its inside to air
+    pub fn hollow_box(&mut self, min: [i32; 3], max: [i32; 3], block: BlockType) -> &mut Self {
+        self.fill(min, max, block);
+        let inner_min = [min[0] + 1, min[1] + 1, min[2] + 1];
+        let inner_max = [max[0] - 1, max[1] - 1, max[2] - 1];
+        self.fill(inner_min, inner_max, BlockType::Air)
+    }
+
+    pub fn blocks(&self) -> impl Iterator<Item = ([i32; 3], BlockType)> + '_ {
+        self.blocks.iter().map(|(&offset, &block)| (offset, block))
+    }
+
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    /// Smallest and largest offsets after transforming, inclusive
+    pub fn bounds(&self, transform: Transform) -> ([i32; 3], [i32; 3]) {
+        let mut min = [i32::MAX; 3];
+        let mut max = [i32::MIN; 3];
+        for offset in self.blocks.keys() {
+            let offset = transform.apply(*offset);
+            for axis in 0..3 {
+                min[axis] = min[axis].min(offset[axis]);
+                max[axis] = max[axis].max(offset[axis]);
+            }
+        }
+        (min, max)
+    }
+
+    /// Furthest any block lies from the origin horizontally, in any orientation
+    pub fn horizontal_reach(&self) -> i32 {
+        self.blocks.keys().map(|[x, _, z]| x.abs().max(z.abs())).max().unwrap_or(0)
+    }
+
+    /// A small wooden hut with a door on the north side
+    pub fn hut() -> Self {
+        let mut hut = Self::new("hut");
+        hut.hollow_box([-3, 0, -3], [3, 4, 3], BlockType::Planks)
+            .fill([-3, 0, -3], [3, 0, 3], BlockType::Cobblestone)
+            .fill([-2, 5, -2], [2, 5, 2], BlockType::Planks)
+            .fill([-1, 6, -1], [1, 6, 1], BlockType::Planks);
+        for (x, z) in [(-3, -3), (3, -3), (-3, 3), (3, 3)] {
+            hut.fill([x, 1, z], [x, 4, z], BlockType::Log);
+        }
+        hut.fill([0, 1, -3], [0, 2, -3], BlockType::Air);
+        hut.set([-3, 2, 0], BlockType::Air).set([3, 2, 0], BlockType::Air);
+        hut.foundation = Some(BlockType::Cobblestone);
+        hut
+    }
+
+    /// Crumbled stone walls around an open courtyard
+    pub fn ruin() -> Self {
+        let mut ruin = Self::new("ruin");
+        ruin.fill([-4, 0, -4], [4, 0, 4], BlockType::Cobblestone);
+        // Wall heights along each side, worn down unevenly
+        let heights = [3, 2, 3, 1, 0, 1, 2, 3, 2];
+        for (i, &height) in heights.iter().enumerate() {
+            let i = i as i32 - 4;
+            for y in 1..=height {
+                ruin.set([i, y, -4], BlockType::Cobblestone);
+                ruin.set([4, y, i], BlockType::Cobblestone);
+            }
+            for y in 1..=(height - 1).max(0) {
+                ruin.set([-i, y, 4], BlockType::Cobblestone);
+            }
+        }
+        ruin.fill([-3, 1, -3], [3, 3, 3], BlockType::Air);
+        ruin.foundation = Some(BlockType::Cobblestone);
+        ruin
+    }
+
+    /// A sealed stone room buried underground
+    pub fn dungeon() -> Self {
+        let mut dungeon = Self::new("dungeon");
+        dungeon.hollow_box([-4, 0, -4], [4, 5, 4], BlockType::Cobblestone);
+        dungeon.fill([-4, 0, -4], [4, 0, 4], BlockType::Cobblestone);
+        for (x, z) in [(-3, -3), (3, 3), (-3, 3)] {
+            dungeon.set([x, 1, z], BlockType::Planks);
+        }
+        dungeon
+    }
+}
+
+/// Where a structure may be placed
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlacementRule {
+    /// Biomes the structure may start in; empty allows every biome
+    pub biomes: Vec<Biome>,
+    /// Size in chunks of the grid cells that each hold at most one structure
+    pub spacing: i32,
+    /// Minimum distance in chunks between two structures of this kind
+    pub separation: i32,
+    /// Largest height difference allowed under the footprint
+    pub max_slope: usize,
+    /// Buried structures are placed inside the rock instead of on the surface
+    pub underground: bool,
+}
+
+/// A template with the rule deciding where it spawns
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructureKind {
+    pub template: StructureTemplate,
+    pub rule: PlacementRule,
+}
+
+/// A structure instance chosen for some location
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlacedStructure {
+    /// Index of the kind in the placer's list
+    pub kind: usize,
+    /// World position of the template origin
+    pub anchor: [i32; 3],
+    pub transform: Transform,
+}
+
+/// Terrain information the placer needs about a column
+pub trait StructureSite {
+    fn biome(&self, x: i32, z: i32) -> Biome;
+    fn surface_height(&self, x: i32, z: i32) -> usize;
+    /// Whether the sea, a river or a lake covers the column
+    fn is_flooded(&self, x: i32, z: i32) -> bool;
+}
+
+/// Picks structure locations on a spacing grid and stamps them into chunks
+///
+/// Every grid cell holds at most one structure of each kind, at a chunk
+/// chosen from the cell's own random stream. A chunk looks up the cells of
+/// all chunks close enough for a structure to reach it, so structures cross
+/// chunk borders and still only depend on the seed.
+pub struct StructurePlacer {
+    seed: u32,
+    sea_level: i32,
+    kinds: Vec<StructureKind>,
+}
+
+impl StructurePlacer {
+    pub fn new(settings: &GeneratorSettings, kinds: Vec<StructureKind>) -> Self {
+        Self {
+            seed: settings.seed,
+            sea_level: settings.sea_level,
+            kinds,
+        }
+    }
+
+    /// Huts in the lowlands, ruins on any land and dungeons underground
+    pub fn default_kinds() -> Vec<StructureKind> {
+        vec![
+            StructureKind {
+                template: StructureTemplate::hut(),
+                rule: PlacementRule {
+                    biomes: vec![Biome::Plains, Biome::Forest, Biome::Tundra],
+                    spacing: 10,
+                    separation: 4,
+                    max_slope: 2,
+                    underground: false,
+                },
+            },
+            StructureKind {
+                template: StructureTemplate::ruin(),
+                rule: PlacementRule {
+                    biomes: vec![Biome::Plains, Biome::Desert, Biome::Forest, Biome::Tundra],
+                    spacing: 14,
+                    separation: 6,
+                    max_slope: 3,
+                    underground: false,
+                },
+            },
+            StructureKind {
+                template: StructureTemplate::dungeon(),
+                rule: PlacementRule {
+                    biomes: Vec::new(),
+                    spacing: 6,
+                    separation: 2,
+                    max_slope: 0,
+                    underground: true,
+                },
+            },
+        ]
+    }
+
+    pub fn kinds(&self) -> &[StructureKind] {
+        &self.kinds
+    }
+
+    /// The structure of each kind in the grid cell holding `chunk`, if its
+    /// start chunk is `chunk` and the terrain there allows it
+    pub fn start_in(
+        &self,
+        kind: usize,
+        chunk: (i32, i32),
+        site: &impl StructureSite,
+    ) -> Option<PlacedStructure> {
+        let StructureKind { template, rule } = &self.kinds[kind];
+        let spacing = rule.spacing.max(1);
+        let cell = (chunk.0.div_euclid(spacing), chunk.1.div_euclid(spacing));
+        let mut rng = Rng::for_chunk(self.seed, cell, STRUCTURE_SALT + kind as u64);
+
+        let spread = (spacing - rule.separation).max(1);
+        let start = (
+            cell.0 * spacing + rng.range(0, spread),
+            cell.1 * spacing + rng.range(0, spread),
+        );
+        let size = CHUNK_SIZE as i32;
+        let (local_x, local_z) = (rng.range(0, size) as usize, rng.range(0, size) as usize);
+        let (x, z) = World::chunk_to_world(start, local_x, local_z);
+        let transform = Transform {
+            rotation: Rotation::ALL[rng.range(0, 4) as usize],
+            mirror: rng.chance(0.5),
+        };
+        let depth_roll = rng.next_f64();
+        if start != chunk {
+            return None;
+        }
+
+        let biome = site.biome(x, z);
+        if !rule.biomes.is_empty() && !rule.biomes.contains(&biome) {
+            return None;
+        }
+
+        let ground = site.surface_height(x, z);
+        let (min, max) = template.bounds(transform);
+        let y = if rule.underground {
+            // Keep a roof of rock between the room and the surface, and a few
+            // layers of stone above the bedrock band
+            let lowest = MIN_Y + BEDROCK_LAYERS as i32 + 3;
+            let highest = ground as i32 - max[1] - 6;
+            if highest < lowest {
+                return None;
+            }
+            lowest + (depth_roll * (highest - lowest + 1) as f64) as i32
+        } else {
+            let corners = [
+                (min[0], min[2]),
+                (min[0], max[2]),
+                (max[0], min[2]),
+                (max[0], max[2]),
+                (0, 0),
+            ];
+            let heights: Vec<usize> = corners
+                .iter()
+                .map(|&(dx, dz)| site.surface_height(x + dx, z + dz))
+                .collect();
+            let lowest = *heights.iter().min().unwrap();
+            let highest = *heights.iter().max().unwrap();
+            if highest - lowest > rule.max_slope || lowest as i32 <= self.sea_level {
+                return None;
+            }
+            // Surface structures need flat, dry ground
+            if corners.iter().any(|&(dx, dz)| site.is_flooded(x + dx, z + dz)) {
+                return None;
+            }
+            ground as i32
+        };
+        if y + max[1] >= MAX_Y {
+            return None;
+        }
+
+        Some(PlacedStructure { kind, anchor: [x, y, z], transform })
+    }
+
+    /// Every structure that may overlap `chunk`
+    pub fn structures_near(
+        &self,
+        chunk: (i32, i32),
+        site: &impl StructureSite,
+    ) -> Vec<PlacedStructure> {
+        let mut placed = Vec::new();
+        for (kind, StructureKind { template, .. }) in self.kinds.iter().enumerate() {
+            let reach = (template.horizontal_reach() + CHUNK_SIZE as i32 - 1) / CHUNK_SIZE as i32;
+            for start_x in chunk.0 - reach..=chunk.0 + reach {
+                for start_z in chunk.1 - reach..=chunk.1 + reach {
+                    placed.extend(self.start_in(kind, (start_x, start_z), site));
+                }
+            }
+        }
+        placed
+    }
+
+    /// Stamps the parts of every nearby structure that fall inside `chunk`
+    pub fn place(&self, chunk: &mut Chunk, site: &impl StructureSite) {
+        for structure in self.structures_near(chunk.position, site) {
+            self.stamp(chunk, structure);
+        }
+    }
+
+    /// Writes the blocks of one structure that fall inside `chunk`
+    pub fn stamp(&self, chunk: &mut Chunk, structure: PlacedStructure) {
+        let template = &self.kinds[structure.kind].template;
+        let (min_x, min_z) = World::chunk_to_world(chunk.position, 0, 0);
+        let [anchor_x, anchor_y, anchor_z] = structure.anchor;
+        let mut lowest: BTreeMap<(usize, usize), i32> = BTreeMap::new();
+
+        for (offset, block) in template.blocks() {
+            let [dx, dy, dz] = structure.transform.apply(offset);
+            let (x, y, z) = (anchor_x + dx - min_x, anchor_y + dy, anchor_z + dz - min_z);
+            let inside = (0..CHUNK_SIZE as i32).contains(&x) && (0..CHUNK_SIZE as i32).contains(&z);
+            if !inside || !(MIN_Y..MAX_Y).contains(&y) {
+                continue;
+            }
+            chunk.set_block_at(x as usize, y, z as usize, block);
+            let column = lowest.entry((x as usize, z as usize)).or_insert(y);
+            *column = (*column).min(y);
+        }
+
+        if let Some(foundation) = template.foundation {
+            for ((x, z), bottom) in lowest {
+                for y in (MIN_Y..bottom).rev().take(MAX_FOUNDATION) {
+                    if chunk.get_block_at(x, y, z).is_some_and(|block| block.is_solid()) {
+                        break;
+                    }
+                    chunk.set_block_at(x, y, z, foundation);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{TerrainGenerator, WorldGenerator};
+    use super::*;
+
+    #[test]
+    fn test_transforms_round_trip() {
+        let offset = [3, 1, -2];
+        let quarter = Transform { rotation: Rotation::Clockwise90, mirror: false };
+        let mut turned = offset;
+        for _ in 0..4 {
+            turned = quarter.apply(turned);
+        }
+        assert_eq!(turned, offset);
+        // North of the origin turns to east
+        assert_eq!(quarter.apply([0, 0, -1]), [1, 0, 0]);
+
+        let mirror = Transform { rotation: Rotation::None, mirror: true };
+        assert_eq!(mirror.apply(mirror.apply(offset)), offset);
+        assert_eq!(mirror.apply(offset), [-3, 1, -2]);
+    }
+
+    #[test]
+    fn test_bounds_follow_rotation() {
+        let mut template = StructureTemplate::new("bar");
+        template.fill([0, 0, 0], [4, 1, 0], BlockType::Planks);
+        assert_eq!(template.bounds(Transform::IDENTITY), ([0, 0, 0], [4, 1, 0]));
+        let turned = Transform { rotation: Rotation::Clockwise90, mirror: false };
+        assert_eq!(template.bounds(turned), ([0, 0, 0], [0, 1, 4]));
+        assert_eq!(template.horizontal_reach(), 4);
+    }
+
+    #[test]
+    fn test_structures_respect_spacing_and_biomes() {
+        let generator = TerrainGenerator::default();
+        let placer = StructurePlacer::new(generator.settings(), StructurePlacer::default_kinds());
+        let site = &generator;
+        for (kind, StructureKind { rule, .. }) in placer.kinds().iter().enumerate() {
+            let mut starts = Vec::new();
+            let mut deep = 0;
+            for x in -40..40 {
+                for z in -40..40 {
+                    if let Some(structure) = placer.start_in(kind, (x, z), site) {
+                        let [x, y, z] = structure.anchor;
+                        if !rule.biomes.is_empty() {
+                            assert!(rule.biomes.contains(&site.biome(x, z)));
+                        }
+                        if y < 0 {
+                            deep += 1;
+                        }
+                        starts.push(World::world_to_chunk(x, z).0);
+                    }
+                }
+            }
+            assert!(!starts.is_empty(), "no {} placed", placer.kinds()[kind].template.name);
+            // Underground structures use the whole depth of the world
+            assert!(!rule.underground || deep > 0);
+            for (i, a) in starts.iter().enumerate() {
+                for b in &starts[i + 1..] {
+                    let distance = (a.0 - b.0).abs().max((a.1 - b.1).abs());
+                    assert!(distance >= rule.separation, "{:?} and {:?} too close", a, b);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_structures_are_stamped_across_chunk_borders() {
+        let generator = TerrainGenerator::default();
+        let placer = StructurePlacer::new(generator.settings(), StructurePlacer::default_kinds());
+        let site = &generator;
+        let structure = (-40..40)
+            .flat_map(|x| (-40..40).map(move |z| (x, z)))
+            .filter_map(|chunk| placer.start_in(0, chunk, site))
+            .find(|structure| {
+                // Pick a hut whose walls cross a chunk border
+                let (_, (x, z)) = World::world_to_chunk(structure.anchor[0], structure.anchor[2]);
+                let reach = placer.kinds()[0].template.horizontal_reach() as usize;
+                let near_edge = |v: usize| v < reach || v >= CHUNK_SIZE - reach;
+                near_edge(x) || near_edge(z)
+            })
+            .expect("no hut placed on a chunk border");
+
+        let [x, y, z] = structure.anchor;
+        let center = World::world_to_chunk(x, z).0;
+        let mut world = World::new();
+        for dx in -1..=1 {
+            for dz in -1..=1 {
+                let mut chunk = Chunk::new((center.0 + dx, center.1 + dz));
+                generator.generate_chunk(&mut chunk);
+                world.insert_chunk(chunk);
+            }
+        }
+
+        for (offset, block) in placer.kinds()[0].template.blocks() {
+            let [dx, dy, dz] = structure.transform.apply(offset);
+            let placed = world.get_block(x + dx, y + dy, z + dz);
+            assert_eq!(placed, Some(block), "at offset {:?}", offset);
+        }
+    }
+}